@@ -0,0 +1,183 @@
+extern crate complex_crystals;
+extern crate rand;
+extern crate rustc_serialize;
+
+use std::env;
+use std::io;
+use std::process::exit;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use complex_crystals::server::GameEngine;
+use complex_crystals::server::server::TICK_TIME;
+use complex_crystals::server::network;
+use complex_crystals::server::config::{GameConfig, PlayerAccount};
+use complex_crystals::server::damage::DamageTable;
+use complex_crystals::server::units::UnitRegistry;
+use complex_crystals::level_generator::generate;
+use rustc_serialize::json;
+
+const USAGE: &'static str = "Usage: dedicated_server [--width N] [--height N] \
+                             [--players Player0,Player1] [--seed N] [--config PATH]\n       \
+                             dedicated_server --hash-password USERNAME < password";
+
+struct Options {
+    width: f64,
+    height: f64,
+    players: Vec<String>,
+    seed: u64,
+    config: String,
+    // Напечатать учётную запись для accounts в конфиге вместо запуска сервера
+    hash_password: Option<String>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Options {
+            width: 1000.0,
+            height: 1000.0,
+            players: vec!["Player0".to_owned(), "Player1".to_owned()],
+            seed: rand::random(),
+            config: "config/engine.json".to_owned(),
+            hash_password: None,
+        };
+        while let Some(flag) = args.next() {
+            let value = match args.next() {
+                Some(value) => value,
+                None => return Err(format!("Missing value for {}", flag)),
+            };
+            match flag.as_str() {
+                "--width" => options.width = parse_value(&flag, &value)?,
+                "--height" => options.height = parse_value(&flag, &value)?,
+                "--seed" => options.seed = parse_value(&flag, &value)?,
+                "--config" => options.config = value,
+                "--hash-password" => options.hash_password = Some(value),
+                "--players" => {
+                    options.players = value
+                        .split(',')
+                        .filter(|name| !name.is_empty())
+                        .map(|name| name.to_owned())
+                        .collect()
+                }
+                _ => return Err(format!("Unknown flag {}", flag)),
+            }
+        }
+        if options.width <= 0.0 || options.height <= 0.0 {
+            return Err("World width and height must be greater than 0".to_owned());
+        }
+        Ok(options)
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    T::from_str(value).map_err(|_| format!("Wrong value for {}: {}", flag, value))
+}
+
+// Читает пароль из stdin и печатает запись для accounts в конфиге
+fn hash_password(username: &str) {
+    eprintln!("Password for {}:", username);
+    let mut password = String::new();
+    if let Err(e) = io::stdin().read_line(&mut password) {
+        println!("Password read error: {}", e);
+        exit(1);
+    }
+    let password = password.trim_end_matches(|c| c == '\n' || c == '\r');
+    if username.is_empty() || password.is_empty() {
+        println!("Username and password must not be empty");
+        exit(2);
+    }
+    println!("{}", json::encode(&PlayerAccount::new(username, password)).unwrap());
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+    if let Some(username) = options.hash_password {
+        hash_password(&username);
+        return;
+    }
+    let config = match GameConfig::new(&options.config) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+    let damage = match DamageTable::new(&config.damage_table) {
+        Ok(damage) => damage,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+    let units = match UnitRegistry::new(&config.units_dir, &damage) {
+        Ok(units) => units,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+    if options.players.len() > config.max_players {
+        println!("Too many players: {}, max_players is {}",
+                 options.players.len(),
+                 config.max_players);
+        exit(1);
+    }
+    // Без учётной записи игрок получит базу, но не сможет ею управлять
+    let missing: Vec<&String> = options.players
+        .iter()
+        .filter(|player| !config.accounts.iter().any(|account| account.username == **player))
+        .collect();
+    if !missing.is_empty() {
+        println!("No accounts for players: {}. Add them to accounts in {}, \
+                  entries are printed by --hash-password USERNAME",
+                 missing
+                     .iter()
+                     .map(|player| player.as_str())
+                     .collect::<Vec<&str>>()
+                     .join(", "),
+                 options.config);
+        exit(1);
+    }
+
+    println!("Seed - {}", options.seed);
+    let engine = Arc::new(Mutex::new(GameEngine::new(options.width,
+                                                     options.height,
+                                                     config,
+                                                     damage,
+                                                     units,
+                                                     options.seed)));
+    generate(engine.clone(), options.width, options.height, options.players);
+
+    let cloned_engine = engine.clone();
+    spawn(move || if let Err(e) = network::start(cloned_engine) {
+              println!("{}", e);
+              exit(1);
+          });
+
+    let tick = Duration::from_millis((TICK_TIME * 1000.0) as u64);
+    let mut last_update = Instant::now();
+    let mut tps_timer = Instant::now();
+    let mut tps = 0;
+    loop {
+        sleep(tick);
+        let now = Instant::now();
+        let elapsed = now.duration_since(last_update);
+        last_update = now;
+
+        let mut engine = engine.lock().unwrap();
+        tps += engine.update(elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9);
+        if tps_timer.elapsed() >= Duration::from_secs(1) {
+            engine.update_tps(tps);
+            tps = 0;
+            tps_timer = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::f64::consts::PI;
+
+use data_types::{ArmorType, CargoType, RadarType, SampleObject, WeaponType};
+use server::damage::DamageTable;
+
+// Компоненты объектов. Описание типа в objects/ перечисляет компоненты
+// списком {"type": "Drive", ...}, у объекта может быть несколько компонентов
+// одного вида, например два оружия
+
+// speed - текущая скорость, dest_x, dest_y - куда едем,
+// energy - расход батареи в секунду движения
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct Drive {
+    pub speed: f64,
+    pub max_speed: f64,
+    pub acceleration: f64,
+    pub turn_rate: f64,
+    pub dest_x: f64,
+    pub dest_y: f64,
+    pub energy: f64,
+}
+
+// energy - расход батареи в секунду работы
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct Radar {
+    pub rtype: RadarType,
+    pub radius: f64,
+    pub energy: f64,
+}
+
+// range - дальность стрельбы, radius - радиус поражения в точке цели,
+// rate - выстрелов в секунду, cooldown - сколько осталось до следующего выстрела,
+// energy - расход батареи за выстрел
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct Weapon {
+    pub wtype: WeaponType,
+    pub radius: f64,
+    pub range: f64,
+    pub damage: f64,
+    pub rate: f64,
+    pub cooldown: f64,
+    pub energy: f64,
+    pub active: bool,
+    pub target_x: f64,
+    pub target_y: f64,
+}
+
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct Cargo {
+    pub ctype: CargoType,
+    pub max: f64,
+    pub current: f64,
+}
+
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct Shell {
+    pub armor: ArmorType,
+    pub health: f64,
+}
+
+// generation - выработка в секунду, charge_radius и charge_rate - зарядка
+// своих объектов в радиусе за счёт собственного заряда
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct Battery {
+    pub current: f64,
+    pub max: f64,
+    pub generation: f64,
+    pub charge_radius: f64,
+    pub charge_rate: f64,
+}
+
+#[derive(Clone, Debug)]
+pub enum Component {
+    Drive(Drive),
+    Radar(Radar),
+    Weapon(Weapon),
+    Cargo(Cargo),
+    Shell(Shell),
+    Battery(Battery),
+}
+
+impl Drive {
+    // Без поворота объект не развернётся к цели и никогда до неё не доедет
+    pub fn can_move(&self) -> bool {
+        self.max_speed > 0.0 && self.acceleration > 0.0 && self.turn_rate > 0.0
+    }
+
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        self.dest_x = x;
+        self.dest_y = y;
+    }
+
+    pub fn stop(&mut self, object: &SampleObject) {
+        self.dest_x = object.x;
+        self.dest_y = object.y;
+        self.speed = 0.0;
+    }
+
+    pub fn arrived(&self, object: &SampleObject) -> bool {
+        (self.dest_x - object.x).hypot(self.dest_y - object.y) <= ARRIVAL_RADIUS
+    }
+
+    // Один шаг движения объекта к dest: поворот не быстрее turn_rate,
+    // разгон и торможение с acceleration так, чтобы остановиться в точке.
+    // Возвращает true, когда объект прибыл
+    pub fn update(&mut self, object: &mut SampleObject, elapsed: f64) -> bool {
+        let dx = self.dest_x - object.x;
+        let dy = self.dest_y - object.y;
+        let dist = (dx * dx + dy * dy).sqrt();
+        if dist <= ARRIVAL_RADIUS.max(self.speed * elapsed) {
+            object.x = self.dest_x;
+            object.y = self.dest_y;
+            self.speed = 0.0;
+            return true;
+        }
+
+        let max_turn = self.turn_rate * elapsed;
+        let turn = normalize_angle(dy.atan2(dx) - object.heading)
+            .max(-max_turn)
+            .min(max_turn);
+        object.heading = normalize_angle(object.heading + turn);
+
+        // Пока нос не смотрит на цель, скорость снижается
+        let alignment = normalize_angle(dy.atan2(dx) - object.heading).cos().max(0.0);
+        let braking_speed = (2.0 * self.acceleration * dist).sqrt();
+        let target_speed = self.max_speed.min(braking_speed) * alignment;
+        let dv = self.acceleration * elapsed;
+        self.speed = if self.speed < target_speed {
+            (self.speed + dv).min(target_speed)
+        } else {
+            (self.speed - dv).max(target_speed)
+        };
+
+        object.x += object.heading.cos() * self.speed * elapsed;
+        object.y += object.heading.sin() * self.speed * elapsed;
+        false
+    }
+}
+
+impl Radar {
+    // Радар, которому нужна энергия, без заряда не работает
+    pub fn online(&self, energy: f64) -> bool {
+        self.radius > 0.0 && self.rtype != RadarType::None &&
+        (self.energy <= 0.0 || energy > 0.0)
+    }
+}
+
+impl Weapon {
+    pub fn fire(&mut self, x: f64, y: f64) {
+        self.target_x = x;
+        self.target_y = y;
+        self.active = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.active = false;
+    }
+
+    pub fn in_range(&self, object: &SampleObject, x: f64, y: f64) -> bool {
+        (x - object.x).hypot(y - object.y) <= self.range
+    }
+
+    // Перезарядка. true, если оружие включено и готово выстрелить
+    pub fn reload(&mut self, elapsed: f64) -> bool {
+        self.cooldown = (self.cooldown - elapsed).max(0.0);
+        self.active && self.cooldown <= 0.0 && self.rate > 0.0
+    }
+
+    pub fn shot(&mut self) {
+        self.cooldown += 1.0 / self.rate;
+    }
+}
+
+impl Cargo {
+    pub fn add(&mut self, size: f64) -> bool {
+        if self.current + size > self.max {
+            return false;
+        }
+        self.current += size;
+        true
+    }
+
+    pub fn remove(&mut self, size: f64) -> bool {
+        if self.current - size < 0.0 {
+            return false;
+        }
+        self.current -= size;
+        true
+    }
+
+    pub fn space(&self) -> f64 {
+        (self.max - self.current).max(0.0)
+    }
+}
+
+impl Shell {
+    pub fn damage(&mut self, table: &DamageTable, wtype: &WeaponType, dmg: f64) {
+        self.health -= dmg * table.multiplier(&self.armor, wtype);
+    }
+}
+
+impl Battery {
+    // Добавляет энергию до max, возвращает сколько добавлено
+    pub fn add(&mut self, size: f64) -> f64 {
+        let added = size.min(self.max - self.current).max(0.0);
+        self.current += added;
+        added
+    }
+
+    // Забирает не больше, чем есть, возвращает сколько забрано
+    pub fn take(&mut self, size: f64) -> f64 {
+        let taken = size.min(self.current).max(0.0);
+        self.current -= taken;
+        taken
+    }
+}
+
+// Расстояние до цели, на котором объект считается прибывшим
+const ARRIVAL_RADIUS: f64 = 0.5;
+
+// Приводит угол к диапазону (-PI, PI]
+fn normalize_angle(angle: f64) -> f64 {
+    let mut angle = angle % (2.0 * PI);
+    if angle > PI {
+        angle -= 2.0 * PI;
+    } else if angle <= -PI {
+        angle += 2.0 * PI;
+    }
+    angle
+}
+
+#[cfg(test)]
+mod tests {
+    use data_types::{ObjectType, SampleObject};
+    use super::Drive;
+
+    fn drive() -> Drive {
+        Drive {
+            speed: 0.0,
+            max_speed: 5.0,
+            acceleration: 1.0,
+            turn_rate: 0.5,
+            dest_x: 0.0,
+            dest_y: 0.0,
+            energy: 0.0,
+        }
+    }
+
+    fn object() -> SampleObject {
+        SampleObject::new(1,
+                          "Player0".to_owned(),
+                          "Builder".to_owned(),
+                          ObjectType::builder(),
+                          0.0,
+                          0.0)
+    }
+
+    // Число шагов до прибытия или None, если объект не доехал
+    fn steps_to_arrive(drive: &mut Drive, object: &mut SampleObject) -> Option<usize> {
+        (1..10000).find(|_| drive.update(object, 0.1))
+    }
+
+    #[test]
+    fn update_arrives_at_destination() {
+        let mut drive = drive();
+        let mut object = object();
+        drive.move_to(30.0, 0.0);
+        assert!(steps_to_arrive(&mut drive, &mut object).is_some());
+        assert_eq!((object.x, object.y), (30.0, 0.0));
+        assert_eq!(drive.speed, 0.0);
+        assert!(drive.arrived(&object));
+    }
+
+    #[test]
+    fn update_turns_to_destination_behind() {
+        let mut drive = drive();
+        let mut object = object();
+        drive.move_to(-20.0, 10.0);
+        assert!(steps_to_arrive(&mut drive, &mut object).is_some());
+        assert_eq!((object.x, object.y), (-20.0, 10.0));
+    }
+
+    #[test]
+    fn update_respects_max_speed() {
+        let mut drive = drive();
+        let mut object = object();
+        drive.move_to(1000.0, 0.0);
+        for _ in 0..200 {
+            assert!(!drive.update(&mut object, 0.1));
+            assert!(drive.speed <= drive.max_speed);
+        }
+        assert!(object.x > 0.0 && object.x < 1000.0);
+    }
+
+    #[test]
+    fn update_at_destination_arrives_immediately() {
+        let mut drive = drive();
+        let mut object = object();
+        drive.move_to(0.2, 0.2);
+        assert!(drive.update(&mut object, 0.1));
+        assert_eq!((object.x, object.y), (0.2, 0.2));
+    }
+}
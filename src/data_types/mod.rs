@@ -1,41 +1,47 @@
-use std::mem::replace;
-use std::collections::HashMap;
-use rustc_serialize::json;
+mod components;
 
-use std::fs::File;
-use std::io::Read;
-use std::sync::{Arc, RwLock};
-use server::server::distance;
+pub use self::components::*;
+
+use rustc_serialize::json::Json;
+use std::collections::VecDeque;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 
 #[derive(RustcDecodable, RustcEncodable, Clone)]
 pub struct ServerInfo {
     pub name: String,
     pub status: String,
     pub tps: u16,
+    pub tick: u64,
+    pub api_version: u32,
+    pub api_versions: Vec<u32>,
 }
 impl ServerInfo {
     pub fn replace(&mut self, new_info: ServerInfo) {
         self.name = new_info.name;
         self.status = new_info.status;
         self.tps = new_info.tps;
+        self.tick = new_info.tick;
+        self.api_version = new_info.api_version;
+        self.api_versions = new_info.api_versions;
     }
 }
 
+// Номер объекта, выдаётся сервером и не меняется, в отличие от имени
+pub type ObjectId = u64;
+
 #[derive(RustcDecodable)]
 pub struct WorldSize {
     pub width: f64,
     pub height: f64,
 }
 
-#[derive(RustcDecodable, RustcEncodable, Clone, PartialEq, Debug)]
-pub enum ObjectType {
-    Asteroid,
-    Builder,
-    Harvester,
-    Battlecruiser,
-}
+// Тип объекта по имени, описания типов загружаются из objects/.
+// Движку известны только Asteroid (источник руды) и Builder (база игрока)
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Debug)]
+pub struct ObjectType(pub String);
 
-#[derive(RustcDecodable, RustcEncodable, Clone)]
+// Порядок важен: чем ниже в списке, тем больше видит радар
+#[derive(RustcDecodable, RustcEncodable, Clone, PartialEq, PartialOrd, Debug)]
 pub enum RadarType {
     None,
     Simple,
@@ -43,31 +49,25 @@ pub enum RadarType {
     Military,
 }
 
-#[derive(RustcDecodable, RustcEncodable, Clone)]
-pub enum WeaponType {
-    None,
-    Mining,
-    Laser,
-}
+// Класс оружия по имени. Классы и их урон задаёт таблица config/damage.json,
+// движку известен только Mining (добыча руды)
+#[derive(Clone, PartialEq, Debug)]
+pub struct WeaponType(pub String);
 
-#[derive(RustcDecodable, RustcEncodable, Clone)]
+#[derive(RustcDecodable, RustcEncodable, Clone, PartialEq, Debug)]
 pub enum CargoType {
     None,
     Mining,
     Battery,
 }
 
-#[derive(RustcDecodable, RustcEncodable, Clone)]
-pub enum ArmorType {
-    Asteroid,
-    Light,
-    Middle,
-    Heavy,
-    Building,
-}
+// Класс брони по имени, строка таблицы config/damage.json
+#[derive(Clone, PartialEq, Debug)]
+pub struct ArmorType(pub String);
 
-#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+#[derive(RustcDecodable, RustcEncodable, Clone, PartialEq, Debug)]
 pub struct ObjectResponse {
+    pub id: ObjectId,
     pub name: String,
     pub owner: String,
     pub x: f64,
@@ -75,194 +75,135 @@ pub struct ObjectResponse {
     pub otype: ObjectType,
 }
 
-#[derive(RustcEncodable)]
-pub struct ObjectInfoRequest {
-    pub name: String,
+#[derive(RustcDecodable, RustcEncodable, Clone, PartialEq, Debug)]
+pub enum OrderType {
+    Move,
+    Attack,
+    Build,
+    Harvest,
+    Patrol,
+    Hold,
+    // Сдать руду из трюма своему Builder поблизости
+    Unload,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum OrderQueue {
+    Orders,
+    Production,
 }
 
+impl OrderType {
+    // Заказы на постройку идут в очередь производства, остальное - в очередь приказов
+    pub fn queue(&self) -> OrderQueue {
+        match *self {
+            OrderType::Build => OrderQueue::Production,
+            _ => OrderQueue::Orders,
+        }
+    }
+}
+
+// Приказ из очереди объекта. x, y - цель приказа, для Patrol - дальний конец
+// маршрута, from_x, from_y - точка, откуда начато патрулирование
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct Order {
+    pub command: u64,
+    pub kind: OrderType,
+    pub x: f64,
+    pub y: f64,
+    pub from_x: f64,
+    pub from_y: f64,
+    pub b_type: Option<ObjectType>,
+    pub b_name: Option<String>,
+    pub b_cost: f64,
+    pub b_time: f64,
+    pub progress: f64,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct ObjectIdRequest {
+    pub id: ObjectId,
+}
+
+// Ответ /object_info: объект и все его компоненты
+#[derive(RustcDecodable, RustcEncodable, Clone)]
+pub struct ObjectInfoResponse {
+    pub object: SampleObject,
+    pub drives: Vec<Drive>,
+    pub radars: Vec<Radar>,
+    pub weapons: Vec<Weapon>,
+    pub cargo: Vec<Cargo>,
+    pub shells: Vec<Shell>,
+    pub batteries: Vec<Battery>,
+}
+
+// Объект мира: положение, владелец и очереди приказов. Двигатель, радар,
+// оружие и остальное хранятся отдельно, как компоненты с номером объекта
 #[derive(RustcDecodable, RustcEncodable, Clone)]
 pub struct SampleObject {
+    pub id: ObjectId,
     pub owner: String,
+    // Имя для отображения, игрок может его менять
     pub name: String,
     pub otype: ObjectType,
     pub x: f64,
     pub y: f64,
+    // Направление движения в радианах
+    pub heading: f64,
 
-    pub drive_speed: f64,
-    pub drive_dest_x: f64,
-    pub drive_dest_y: f64,
-
-    pub radar_radius: f64,
-    pub radar_type: RadarType,
-
-    pub weapon_active: bool,
-    pub weapon_type: WeaponType,
-    pub weapon_radius: f64,
-    pub weapon_target_x: f64,
-    pub weapon_target_y: f64,
-
-    pub cargo_type: CargoType,
-    pub cargo_max: f64,
-    pub cargo_current: f64,
-
-    pub shell_health: f64,
-    pub shell_type: ArmorType,
+    // Первый приказ выполняется, остальные ждут своей очереди
+    pub orders: VecDeque<Order>,
+    // Заказы на постройку, выполняются одновременно с приказами
+    pub production: VecDeque<Order>,
 }
 
 impl SampleObject {
-    pub fn new_empty() -> Self {
+    pub fn new(id: ObjectId,
+               owner: String,
+               name: String,
+               otype: ObjectType,
+               x: f64,
+               y: f64)
+               -> Self {
         SampleObject {
-            owner: "none".to_owned(),
-            name: "none".to_owned(),
-            otype: ObjectType::Asteroid,
-            x: 0.0,
-            y: 0.0,
-
-            drive_speed: 0.0,
-            drive_dest_x: 0.0,
-            drive_dest_y: 0.0,
-
-            radar_radius: 0.0,
-            radar_type: RadarType::None,
-
-            weapon_active: false,
-            weapon_type: WeaponType::None,
-            weapon_radius: 0.0,
-            weapon_target_x: 0.0,
-            weapon_target_y: 0.0,
-
-            cargo_type: CargoType::None,
-            cargo_max: 0.0,
-            cargo_current: 0.0,
-
-            shell_health: 0.0,
-            shell_type: ArmorType::Asteroid,
+            id: id,
+            owner: owner,
+            name: name,
+            otype: otype,
+            x: x,
+            y: y,
+            heading: 0.0,
+            orders: VecDeque::new(),
+            production: VecDeque::new(),
         }
     }
-    pub fn replace_object(&mut self, new: SampleObject) {
-        replace(self, new);
-    }
-    pub fn new(owner: String, name: String, otype: ObjectType, x: f64, y: f64) -> Self {
-        let mut object: SampleObject = match otype {
-            ObjectType::Asteroid => json::decode(&read_file("objects/asteroid.json")).unwrap(),
-            ObjectType::Harvester => json::decode(&read_file("objects/harvester.json")).unwrap(),
-            ObjectType::Battlecruiser => {
-                json::decode(&read_file("objects/battlecruiser.json")).unwrap()
-            }
-            ObjectType::Builder => json::decode(&read_file("objects/builder.json")).unwrap(),
-        };
-        object.owner = owner;
-        object.name = name;
-        object.x = x;
-        object.y = y;
-        object.drive_dest_x = x;
-        object.drive_dest_y = y;
-        object
-    }
-
-    pub fn drive_move_to(&mut self, x: f64, y: f64) {
-        self.drive_dest_x = x;
-        self.drive_dest_y = y;
-    }
 
-    pub fn radar_scan(&self,
-                      objects: HashMap<String, Arc<RwLock<SampleObject>>>)
-                      -> Option<Vec<Arc<RwLock<SampleObject>>>> {
-        let result: Vec<Arc<RwLock<SampleObject>>> = objects
-            .iter()
-            .filter_map(|x| {
-                let (_, v) = x;
-                let object = v.read().unwrap();
-                if distance(self.x, self.y, object.x, object.y) <= self.radar_radius {
-                    Some(v.clone())
-                } else {
-                    None
-                }
-            })
-            .collect();
-        Some(result)
+    pub fn current_order(&self) -> Option<&Order> {
+        self.orders.front()
     }
 
-    pub fn weapon_fire(&mut self, x: f64, y: f64) {
-        self.weapon_target_x = x;
-        self.weapon_target_y = y;
-        self.weapon_active = true;
-    }
-
-    pub fn weapon_stop(&mut self) {
-        self.weapon_active = false;
-    }
-
-    pub fn cargo_add(&mut self, size: f64) -> bool {
-        if self.cargo_current + size > self.cargo_max {
-            return false;
-        }
-        self.cargo_current += size;
-        true
-    }
-
-    pub fn cargo_remove(&mut self, size: f64) -> bool {
-        if self.cargo_current - size < 0.0 {
-            return false;
+    pub fn queue(&self, queue: &OrderQueue) -> &VecDeque<Order> {
+        match *queue {
+            OrderQueue::Orders => &self.orders,
+            OrderQueue::Production => &self.production,
         }
-        self.cargo_current -= size;
-        true
     }
 
-    pub fn shell_damage(&mut self, wtype: WeaponType, dmg: f64) {
-        match self.shell_type {
-            ArmorType::Asteroid => {
-                match wtype {
-                    WeaponType::None => {}
-                    WeaponType::Mining => self.shell_health -= dmg,
-                    WeaponType::Laser => self.shell_health -= dmg,
-                }
-            }
-            ArmorType::Building => {
-                match wtype {
-                    WeaponType::None => {}
-                    WeaponType::Mining => self.shell_health -= dmg * 0.0,
-                    WeaponType::Laser => self.shell_health -= dmg * 0.001,
-                }
-            }
-            ArmorType::Heavy => {
-                match wtype {
-                    WeaponType::None => {}
-                    WeaponType::Mining => self.shell_health -= dmg * 0.0,
-                    WeaponType::Laser => self.shell_health -= dmg * 0.01,
-                }
-            }
-            ArmorType::Middle => {
-                match wtype {
-                    WeaponType::None => {}
-                    WeaponType::Mining => self.shell_health -= dmg * 0.0,
-                    WeaponType::Laser => self.shell_health -= dmg * 0.1,
-                }
-            }
-            ArmorType::Light => {
-                match wtype {
-                    WeaponType::None => {}
-                    WeaponType::Mining => self.shell_health -= dmg * 0.001,
-                    WeaponType::Laser => self.shell_health -= dmg * 1.0,
-                }
-            }
+    pub fn queue_mut(&mut self, queue: &OrderQueue) -> &mut VecDeque<Order> {
+        match *queue {
+            OrderQueue::Orders => &mut self.orders,
+            OrderQueue::Production => &mut self.production,
         }
     }
 
-    pub fn engine_update(&mut self, elapsed: f64) {
-        if !((self.x - self.drive_dest_x).abs() < ::FLOAT_ERR) {
-            if self.x < self.drive_dest_x {
-                self.x += self.drive_speed * elapsed;
-            } else if self.x > self.drive_dest_x {
-                self.x -= self.drive_speed * elapsed;
-            }
-        }
-        if !((self.y - self.drive_dest_y).abs() < ::FLOAT_ERR) {
-            if self.y < self.drive_dest_y {
-                self.y += self.drive_speed * elapsed;
-            } else if self.y > self.drive_dest_y {
-                self.y -= self.drive_speed * elapsed;
-            }
+    pub fn to_response(&self) -> ObjectResponse {
+        ObjectResponse {
+            id: self.id,
+            name: self.name.clone(),
+            owner: self.owner.clone(),
+            x: self.x,
+            y: self.y,
+            otype: self.otype.clone(),
         }
     }
 
@@ -276,13 +217,36 @@ impl SampleObject {
 }
 
 impl ObjectType {
+    pub fn asteroid() -> Self {
+        ObjectType("Asteroid".to_owned())
+    }
+
+    pub fn builder() -> Self {
+        ObjectType("Builder".to_owned())
+    }
+
+    pub fn is_asteroid(&self) -> bool {
+        self.0 == "Asteroid"
+    }
+
+    pub fn is_builder(&self) -> bool {
+        self.0 == "Builder"
+    }
+
     pub fn to_string(&self) -> String {
-        match *self {
-            ObjectType::Asteroid => "Asteroid".to_string(),
-            ObjectType::Builder => "Builder".to_string(),
-            ObjectType::Harvester => "Harvester".to_string(),
-            ObjectType::Battlecruiser => "Battlecruiser".to_string(),
-        }
+        self.0.clone()
+    }
+}
+
+impl Decodable for ObjectType {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
+        d.read_str().map(ObjectType)
+    }
+}
+
+impl Encodable for ObjectType {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_str(&self.0)
     }
 }
 
@@ -298,12 +262,25 @@ impl RadarType {
 }
 
 impl WeaponType {
+    pub fn is_mining(&self) -> bool {
+        self.0 == "Mining"
+    }
+
     pub fn to_string(&self) -> String {
-        match *self {
-            WeaponType::None => "None".to_string(),
-            WeaponType::Laser => "Laser".to_string(),
-            WeaponType::Mining => "Mining".to_string(),
-        }
+        self.0.clone()
+    }
+}
+
+// В JSON класс оружия и брони - просто строка
+impl Decodable for WeaponType {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
+        d.read_str().map(WeaponType)
+    }
+}
+
+impl Encodable for WeaponType {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_str(&self.0)
     }
 }
 
@@ -319,26 +296,23 @@ impl CargoType {
 
 impl ArmorType {
     pub fn to_string(&self) -> String {
-        match *self {
-            ArmorType::Asteroid => "Asteroid".to_string(),
-            ArmorType::Building => "Building".to_string(),
-            ArmorType::Heavy => "Heavy".to_string(),
-            ArmorType::Light => "Light".to_string(),
-            ArmorType::Middle => "Middle".to_string(),
-        }
+        self.0.clone()
+    }
+}
+
+impl Decodable for ArmorType {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
+        d.read_str().map(ArmorType)
     }
 }
-fn read_file(path: &str) -> String {
-    let mut file = match File::open(path) {
-        Ok(data) => data,
-        Err(e) => panic!("Game config file open error: {:?}", e),
-    };
-    let mut string = String::new();
-    file.read_to_string(&mut string).unwrap();
-    string
+
+impl Encodable for ArmorType {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_str(&self.0)
+    }
 }
 
-#[derive(RustcEncodable)]
+#[derive(RustcDecodable, RustcEncodable)]
 pub struct WorldSizeResponse {
     pub width: f64,
     pub height: f64,
@@ -346,26 +320,165 @@ pub struct WorldSizeResponse {
 
 #[derive(RustcDecodable, RustcEncodable)]
 pub struct MoveObjectRequest {
-    pub name: String,
+    pub id: ObjectId,
     pub x: f64,
     pub y: f64,
+    pub append: Option<bool>,
 }
 
-#[derive(RustcDecodable)]
-pub struct NameResponse {
+// Новое имя для отображения
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct RenameRequest {
+    pub id: ObjectId,
     pub name: String,
 }
 
 #[derive(RustcDecodable)]
 pub struct WeaponFireRequest {
-    pub name: String,
+    pub id: ObjectId,
     pub x: f64,
     pub y: f64,
+    pub append: Option<bool>,
 }
 
 #[derive(RustcDecodable)]
 pub struct BuildRequest {
-    pub name: String,
+    pub id: ObjectId,
     pub oname: String,
     pub otype: ObjectType,
+    pub append: Option<bool>,
+}
+
+// Приказ любого типа. append - добавить в конец очереди вместо её замены,
+// x, y не нужны для Hold, Unload и Build, oname (имя нового объекта)
+// и otype нужны только для Build
+#[derive(RustcDecodable)]
+pub struct OrderRequest {
+    pub id: ObjectId,
+    pub kind: OrderType,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub oname: Option<String>,
+    pub otype: Option<ObjectType>,
+    pub append: Option<bool>,
+}
+
+#[derive(RustcDecodable, RustcEncodable, Debug)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub message: String,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct CommandResponse {
+    pub command: u64,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct CommandStatusRequest {
+    pub command: u64,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct CommandStatusResponse {
+    pub command: u64,
+    pub status: String,
+    // Номер построенного объекта для завершённой постройки
+    pub object: Option<ObjectId>,
+    pub error: Option<ErrorResponse>,
+}
+
+#[derive(RustcDecodable, RustcEncodable, Clone)]
+pub struct GameEventResponse {
+    pub kind: String,
+    pub id: ObjectId,
+}
+
+#[derive(RustcDecodable, RustcEncodable, Clone)]
+pub struct WorldUpdate {
+    pub revision: u64,
+    pub changed: Vec<ObjectResponse>,
+    pub removed: Vec<ObjectId>,
+    pub events: Vec<GameEventResponse>,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct UpdatesRequest {
+    pub since: Option<u64>,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct UpdatesResponse {
+    pub revision: u64,
+    pub reset: bool,
+    pub updates: Vec<WorldUpdate>,
+}
+
+// Simple - только координаты, Middle - ещё номер, тип и владелец, Military - всё
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct ContactResponse {
+    pub x: f64,
+    pub y: f64,
+    pub id: Option<ObjectId>,
+    pub otype: Option<ObjectType>,
+    pub owner: Option<String>,
+    pub shell_health: Option<f64>,
+    pub cargo_current: Option<f64>,
+    pub orders: Option<OrdersResponse>,
+}
+
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct OrdersResponse {
+    pub drive_dest_x: f64,
+    pub drive_dest_y: f64,
+    pub weapon_active: bool,
+    pub weapon_target_x: f64,
+    pub weapon_target_y: f64,
+    pub order: Option<Order>,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct BatchResult {
+    pub command: Option<u64>,
+    pub error: Option<ErrorResponse>,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct BatchResponse {
+    pub accepted: bool,
+    pub results: Vec<BatchResult>,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct PlayerResponse {
+    pub name: String,
+    pub resources: f64,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct PlayerLimitsResponse {
+    pub player: String,
+    pub tokens: f64,
+    pub pending: usize,
+    pub accepted: u64,
+    pub limited: u64,
+}
+
+// Описание маршрута для /api, request и response - схемы JSON
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct ApiRouteResponse {
+    pub path: String,
+    pub version: u32,
+    pub methods: Vec<String>,
+    pub auth: String,
+    pub request: Option<Json>,
+    pub response: Json,
+}
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct ApiResponse {
+    pub versions: Vec<u32>,
+    pub legacy_version: u32,
+    pub routes: Vec<ApiRouteResponse>,
+    pub error: Json,
 }
@@ -4,7 +4,7 @@ use piston::input::*;
 
 use scenes::main_menu::MainMenuScene;
 use scenes::game::GameScene;
-use network::{ServerClient, ServerManager};
+use network::{ServerClient, ServerManager, UpdateMode};
 
 pub struct Engine {
     pub window: PistonWindow,
@@ -31,22 +31,25 @@ impl Engine {
             SceneAction::None => {}
             SceneAction::ConnectToServer(addr) => {
                 self.scene = if let Some(scene) =
-                    GameScene::new(&mut self.window, Box::new(ServerClient::new(addr))) {
+                    GameScene::new(&mut self.window,
+                                   Box::new(ServerClient::new(addr, UpdateMode::Streaming))) {
                     Box::new(scene)
                 } else {
                     Box::new(MainMenuScene::new(&mut self.window))
                 }
             }
             SceneAction::StartServer(opt) => {
-                self.scene = if let Some(scene) =
-                    GameScene::new(&mut self.window,
-                                   Box::new(ServerManager::new(opt.0 as f64,
-                                                               opt.1 as f64,
-                                                               (0..opt.2)
-                                                                   .map(|x| {
-                                                                            format!("Player{}", x)
-                                                                        })
-                                                                   .collect()))) {
+                let players = (0..opt.2).map(|x| format!("Player{}", x)).collect();
+                let manager = match ServerManager::new(opt.0 as f64, opt.1 as f64, players) {
+                    Ok(manager) => manager,
+                    Err(e) => {
+                        println!("{}", e);
+                        self.scene = Box::new(MainMenuScene::new(&mut self.window));
+                        return;
+                    }
+                };
+                self.scene = if let Some(scene) = GameScene::new(&mut self.window,
+                                                                 Box::new(manager)) {
                     Box::new(scene)
                 } else {
                     Box::new(MainMenuScene::new(&mut self.window))
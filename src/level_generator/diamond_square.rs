@@ -1,6 +1,6 @@
-use rand::random;
+use rand::Rng;
 
-pub fn generate_ds(size: usize) -> Vec<Vec<f64>> {
+pub fn generate_ds<R: Rng>(size: usize, rng: &mut R) -> Vec<Vec<f64>> {
     // Генерация массива
     let mut array = Vec::new();
     for _ in 0..size {
@@ -12,15 +12,19 @@ pub fn generate_ds(size: usize) -> Vec<Vec<f64>> {
     }
 
     // Случайные точки по углам
-    array[0][0] = random::<f64>();
-    array[size - 1][size - 1] = random::<f64>();
-    array[0][size - 1] = random::<f64>();
-    array[size - 1][0] = random::<f64>();
+    array[0][0] = rng.gen::<f64>();
+    array[size - 1][size - 1] = rng.gen::<f64>();
+    array[0][size - 1] = rng.gen::<f64>();
+    array[size - 1][0] = rng.gen::<f64>();
 
-    recursive(size - 1, size - 1, array)
+    recursive(size - 1, size - 1, array, rng)
 }
 
-fn recursive(world_size: usize, step_size: usize, mut array: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+fn recursive<R: Rng>(world_size: usize,
+                    step_size: usize,
+                    mut array: Vec<Vec<f64>>,
+                    rng: &mut R)
+                    -> Vec<Vec<f64>> {
     if step_size == 1 {
         return array; // Выход из рекурсии
     }
@@ -29,7 +33,7 @@ fn recursive(world_size: usize, step_size: usize, mut array: Vec<Vec<f64>>) -> V
     // Обход Square
     while y < world_size {
         while x < world_size {
-            step_square(x, y, step_size, &mut array);
+            step_square(x, y, step_size, &mut array, rng);
             x += step_size;
         }
         y += step_size;
@@ -43,28 +47,32 @@ fn recursive(world_size: usize, step_size: usize, mut array: Vec<Vec<f64>>) -> V
             step_diamond(x as isize - (step_size / 2) as isize,
                          y as isize,
                          step_size,
-                         &mut array);
+                         &mut array,
+                         rng);
             step_diamond(x as isize,
                          y as isize - (step_size / 2) as isize,
                          step_size,
-                         &mut array);
+                         &mut array,
+                         rng);
             step_diamond(x as isize + (step_size / 2) as isize,
                          y as isize,
                          step_size,
-                         &mut array);
+                         &mut array,
+                         rng);
             step_diamond(x as isize,
                          y as isize + (step_size / 2) as isize,
                          step_size,
-                         &mut array);
+                         &mut array,
+                         rng);
             x += step_size;
         }
         y += step_size;
         x = 0;
     }
-    recursive(world_size, step_size / 2, array)
+    recursive(world_size, step_size / 2, array, rng)
 }
 
-fn step_square(x: usize, y: usize, size: usize, array: &mut Vec<Vec<f64>>) {
+fn step_square<R: Rng>(x: usize, y: usize, size: usize, array: &mut Vec<Vec<f64>>, rng: &mut R) {
     // square
     // a     b
     //    x
@@ -75,12 +83,16 @@ fn step_square(x: usize, y: usize, size: usize, array: &mut Vec<Vec<f64>>) {
     let c = array[x + size][y];
     let d = array[x + size][y + size];
     let center = (a + b + c + d) / 4.0;
-    let random = (-center + random::<f64>() % center * 2.0) * 1.0;
+    let random = (-center + rng.gen::<f64>() % center * 2.0) * 1.0;
 
     array[x + (size / 2)][y + (size / 2)] = center + random;
 }
 
-fn step_diamond(x: isize, y: isize, size: usize, array: &mut Vec<Vec<f64>>) {
+fn step_diamond<R: Rng>(x: isize,
+                        y: isize,
+                        size: usize,
+                        array: &mut Vec<Vec<f64>>,
+                        rng: &mut R) {
     let hs = size / 2;
     // diamond
     //    b
@@ -92,7 +104,7 @@ fn step_diamond(x: isize, y: isize, size: usize, array: &mut Vec<Vec<f64>>) {
     let c = get_element(x + size as isize, y + hs as isize, &array);
     let d = get_element(x + hs as isize, y + size as isize, &array);
     let center = (a + b + c + d) / 4.0;
-    let random = (-center + random::<f64>() % center * 2.0) * 1.0;
+    let random = (-center + rng.gen::<f64>() % center * 2.0) * 1.0;
 
     array[(x + hs as isize) as usize][(y + hs as isize) as usize] = center + random;
 }
@@ -14,20 +14,23 @@ pub fn generate(mutex: Arc<Mutex<GameEngine>>,
     let coeff_height = map_height / noise_size as f64;
 
     print!("Генерация астероидов... ");
-    let asteroid_noise = diamond_square::generate_ds(noise_size);
-    let mut debug_asteroid_count = 0;
-
     let mut engine = mutex.lock().unwrap();
+    let asteroid_noise = diamond_square::generate_ds(noise_size, &mut engine.rng);
+    let mut debug_asteroid_count = 0;
 
     for y in 0..noise_size {
         for x in 0..noise_size {
             let level = asteroid_noise[x][y].powf(2.0);
             if level > 1.0 {
-                engine.add_object(format!("Asteroid{}{}", &*x.to_string(), &*y.to_string()),
-                                  x as f64 * coeff_width,
-                                  y as f64 * coeff_height,
-                                  ObjectType::Asteroid,
-                                  "unknown".to_owned());
+                let added = engine.add_object(format!("Asteroid{}_{}", x, y),
+                                              x as f64 * coeff_width,
+                                              y as f64 * coeff_height,
+                                              ObjectType::asteroid(),
+                                              "unknown".to_owned());
+                if let Err(e) = added {
+                    println!("{}", e.message());
+                    continue;
+                }
                 debug_asteroid_count += 1;
             }
         }
@@ -37,11 +40,15 @@ pub fn generate(mutex: Arc<Mutex<GameEngine>>,
     for (i, player) in players.iter().enumerate() {
         let mut basename = player.clone();
         basename.push_str("Base");
-        engine.add_object(basename.to_owned(),
-                          100.0,
-                          100.0 * i as f64,
-                          ObjectType::Builder,
-                          player.clone());
+        if let Err(e) = engine.add_object(basename.to_owned(),
+                                          100.0,
+                                          100.0 * i as f64,
+                                          ObjectType::builder(),
+                                          player.clone()) {
+            println!("{}", e.message());
+        }
+        let start_resources = engine.config.start_resources;
+        engine.resources.insert(player.clone(), start_resources);
     }
 }
 
@@ -0,0 +1,11 @@
+extern crate iron;
+extern crate rand;
+extern crate rustc_serialize;
+extern crate sha2;
+extern crate pbkdf2;
+
+pub mod server;
+pub mod level_generator;
+pub mod data_types;
+
+pub const FLOAT_ERR: f64 = std::f64::EPSILON;
@@ -3,7 +3,6 @@ extern crate graphics;
 extern crate piston_window;
 extern crate find_folder;
 extern crate time;
-extern crate iron;
 
 #[macro_use]
 extern crate conrod;
@@ -12,16 +11,15 @@ extern crate rand;
 extern crate rustc_serialize;
 extern crate hyper;
 
+extern crate complex_crystals;
+
+use complex_crystals::{server, level_generator, data_types};
+
 mod network;
 mod engine;
 pub mod scenes;
 mod utils;
 mod ui;
-mod server;
-mod level_generator;
-mod data_types;
-
-const FLOAT_ERR: f64 = std::f64::EPSILON;
 
 fn main() {
     ::engine::spawn();
@@ -1,18 +1,20 @@
 pub mod server_client;
 pub mod server_manager;
 
-pub use self::server_client::ServerClient;
+pub use self::server_client::{ServerClient, UpdateMode};
 pub use self::server_manager::ServerManager;
 
 use std::collections::HashMap;
-use data_types::{SampleObject, ObjectResponse, ServerInfo};
+use data_types::{ObjectInfoResponse, ObjectId, ObjectResponse, ServerInfo};
 
 pub trait ServerConnection {
     fn update(&mut self, elapsed: f64);
     fn check_connection(&self) -> Option<ServerInfo>;
 
-    fn get_objects(&self) -> HashMap<String, ObjectResponse>;
-    fn select_object(&mut self, name: String);
-    fn get_selected_object(&self) -> Option<SampleObject>;
+    fn get_objects(&self) -> HashMap<ObjectId, ObjectResponse>;
+    // Объекты не дальше radius от точки мира
+    fn objects_at(&self, x: f64, y: f64, radius: f64) -> Vec<ObjectResponse>;
+    fn select_object(&mut self, id: ObjectId);
+    fn get_selected_object(&self) -> Option<ObjectInfoResponse>;
     fn get_server_info(&self) -> ServerInfo;
 }
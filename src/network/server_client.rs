@@ -5,18 +5,39 @@ use hyper::header::{Authorization, Basic, Headers};
 use std::io::Read;
 use std::thread;
 use std::thread::JoinHandle;
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::time::Duration;
 use rustc_serialize::json;
-use data_types::{SampleObject, ObjectResponse, ServerInfo, ObjectInfoRequest};
+use data_types::{ObjectInfoResponse, ObjectId, ObjectResponse, ServerInfo, ObjectIdRequest,
+                 UpdatesRequest, UpdatesResponse};
+use server::spatial::{SpatialGrid, DEFAULT_CELL_SIZE};
 
 use network::ServerConnection;
 
-const OBJECTS_UPDATE_ADDR: &'static str = "/objects";
-const OBJECTINFO_ADDR: &'static str = "/object_info";
-const SERVERINFO_UPDATE_ADDR: &'static str = "/info";
-const USERNAME: &'static str = "admin";
+const OBJECTS_UPDATE_ADDR: &'static str = "/v1/objects";
+const OBJECTINFO_ADDR: &'static str = "/v1/object_info";
+const SERVERINFO_UPDATE_ADDR: &'static str = "/v1/info";
+const UPDATES_ADDR: &'static str = "/v1/updates";
+
+// Polling - опрос /objects раз в 3 секунды, Streaming - long-poll подписка на /updates.
+// Оба показывают игроку то же, что видят его радары, администратору - весь мир
+#[derive(Clone, PartialEq)]
+pub enum UpdateMode {
+    Polling,
+    Streaming,
+}
+
+#[derive(Clone)]
+struct Credentials {
+    username: String,
+    password: String,
+}
 
 pub struct ServerClient {
     addr: String,
+    credentials: Credentials,
+    mode: UpdateMode,
+    stream: Option<Receiver<UpdatesResponse>>,
     update_timer: f64,
     thread_check_timer: f64,
 
@@ -24,19 +45,44 @@ pub struct ServerClient {
     df_selected_object: Arc<Mutex<bool>>,
     df_server_info: Arc<Mutex<bool>>,
 
-    jh_objects: Option<JoinHandle<HashMap<String, ObjectResponse>>>,
-    jh_selected_object: Option<JoinHandle<SampleObject>>,
+    jh_objects: Option<JoinHandle<HashMap<ObjectId, ObjectResponse>>>,
+    jh_selected_object: Option<JoinHandle<ObjectInfoResponse>>,
     jh_server_info: Option<JoinHandle<ServerInfo>>,
 
-    selected_object: Option<SampleObject>,
-    objects: HashMap<String, ObjectResponse>,
+    selected_object: Option<ObjectInfoResponse>,
+    objects: HashMap<ObjectId, ObjectResponse>,
+    // Индекс objects для выбора объектов мышью
+    spatial: SpatialGrid<ObjectId>,
     server_info: ServerInfo,
 }
 
 impl ServerClient {
-    pub fn new(addr: String) -> Self {
+    // Адрес в формате login:password@host:port
+    pub fn new(addr: String, mode: UpdateMode) -> Self {
+        let (credentials, addr) = match addr.rfind('@') {
+            Some(pos) => {
+                let (login, host) = addr.split_at(pos);
+                let mut login = login.splitn(2, ':');
+                (Credentials {
+                     username: login.next().unwrap_or("").to_owned(),
+                     password: login.next().unwrap_or("").to_owned(),
+                 },
+                 host[1..].to_owned())
+            }
+            None => {
+                println!("Не указаны логин и пароль, адрес: login:password@host:port");
+                (Credentials {
+                     username: String::new(),
+                     password: String::new(),
+                 },
+                 addr)
+            }
+        };
         ServerClient {
             addr: addr,
+            credentials: credentials,
+            mode: mode,
+            stream: None,
             update_timer: 3.0,
             thread_check_timer: 0.0,
 
@@ -50,37 +96,88 @@ impl ServerClient {
 
             selected_object: None,
             objects: HashMap::new(),
+            spatial: SpatialGrid::new(DEFAULT_CELL_SIZE),
             server_info: ServerInfo {
                 name: "ServerName".to_owned(),
                 status: "SomeStatus".to_owned(),
                 tps: 0,
+                tick: 0,
+                api_version: 0,
+                api_versions: vec![],
             },
         }
     }
+
+    fn apply_updates(&mut self, response: UpdatesResponse) {
+        if response.reset {
+            self.objects.clear();
+            self.spatial.clear();
+        }
+        for update in response.updates {
+            for object in update.changed {
+                self.spatial.insert(object.id, object.x, object.y);
+                self.objects.insert(object.id, object);
+            }
+            for id in update.removed {
+                self.spatial.remove(&id);
+                self.objects.remove(&id);
+            }
+        }
+    }
+
+    fn set_objects(&mut self, objects: HashMap<ObjectId, ObjectResponse>) {
+        self.spatial.clear();
+        for object in objects.values() {
+            self.spatial.insert(object.id, object.x, object.y);
+        }
+        self.objects = objects;
+    }
 }
 
 impl ServerConnection for ServerClient {
     fn update(&mut self, elapsed: f64) {
         self.update_timer += elapsed;
         self.thread_check_timer += elapsed;
+        if self.mode == UpdateMode::Streaming {
+            if self.stream.is_none() {
+                let addr = Url::parse(&format!("http://{}{}", self.addr, UPDATES_ADDR)).unwrap();
+                let credentials = self.credentials.clone();
+                let (sender, receiver) = channel();
+                self.stream = Some(receiver);
+                thread::spawn(move || NetworkRequest::stream_updates(sender, addr, credentials));
+            }
+            let mut received = vec![];
+            if let Some(ref stream) = self.stream {
+                while let Ok(response) = stream.try_recv() {
+                    received.push(response);
+                }
+            }
+            for response in received {
+                self.apply_updates(response);
+            }
+        }
+
         if self.update_timer >= 3.0 {
             self.update_timer = 0.0;
             // Update objects
-            if self.jh_objects.is_none() {
+            if self.mode == UpdateMode::Polling && self.jh_objects.is_none() {
                 let addr = Url::parse(&format!("http://{}{}", self.addr, OBJECTS_UPDATE_ADDR))
                     .unwrap();
                 let flag_mutex = self.df_objects.clone();
-                self.jh_objects =
-                    Some(thread::spawn(move || NetworkRequest::update_objects(flag_mutex, addr)));
+                let credentials = self.credentials.clone();
+                self.jh_objects = Some(thread::spawn(move || {
+                    NetworkRequest::update_objects(flag_mutex, addr, credentials)
+                }));
             }
 
             // Update server info
             if self.jh_server_info.is_none() {
                 let addr = Url::parse(&format!("http://{}{}", self.addr, SERVERINFO_UPDATE_ADDR))
                     .unwrap();
-                let flag_mutex = self.df_objects.clone();
+                let flag_mutex = self.df_server_info.clone();
+                let credentials = self.credentials.clone();
                 self.jh_server_info = Some(thread::spawn(move || {
-                    NetworkRequest::update_server_info(flag_mutex, addr)
+                    NetworkRequest::update_server_info(flag_mutex, addr, credentials)
                 }));
             }
         }
@@ -88,9 +185,14 @@ impl ServerConnection for ServerClient {
         if self.thread_check_timer >= 0.1 {
             self.thread_check_timer = 0.0;
             // When threads is end
-            let mut df_objects = self.df_objects.lock().unwrap();
+            let df_objects = self.df_objects.clone();
+            let mut df_objects = df_objects.lock().unwrap();
             if *df_objects == true {
-                self.objects = self.jh_objects.take().unwrap().join().unwrap();
+                // В режиме Streaming поток опроса /objects не запускается
+                if let Some(jh) = self.jh_objects.take() {
+                    let objects = jh.join().unwrap();
+                    self.set_objects(objects);
+                }
                 *df_objects = false;
             }
 
@@ -110,23 +212,34 @@ impl ServerConnection for ServerClient {
     }
     fn check_connection(&self) -> Option<ServerInfo> {
         let addr = Url::parse(&format!("http://{}{}", self.addr, SERVERINFO_UPDATE_ADDR)).unwrap();
-        thread::spawn(move || NetworkRequest::check_server_info(addr))
+        let credentials = self.credentials.clone();
+        thread::spawn(move || NetworkRequest::check_server_info(addr, credentials))
             .join()
             .ok()
     }
 
-    fn get_objects(&self) -> HashMap<String, ObjectResponse> {
+    fn get_objects(&self) -> HashMap<ObjectId, ObjectResponse> {
         self.objects.clone()
     }
 
-    fn select_object(&mut self, name: String) {
+    fn objects_at(&self, x: f64, y: f64, radius: f64) -> Vec<ObjectResponse> {
+        self.spatial
+            .query(x, y, radius)
+            .into_iter()
+            .filter_map(|id| self.objects.get(id).cloned())
+            .collect()
+    }
+
+    fn select_object(&mut self, id: ObjectId) {
         let addr = Url::parse(&format!("http://{}{}", self.addr, OBJECTINFO_ADDR)).unwrap();
         let flag_mutex = self.df_selected_object.clone();
-        self.jh_selected_object =
-            Some(thread::spawn(move || NetworkRequest::select_object(name, flag_mutex, addr)))
+        let credentials = self.credentials.clone();
+        self.jh_selected_object = Some(thread::spawn(move || {
+            NetworkRequest::select_object(id, flag_mutex, addr, credentials)
+        }))
     }
 
-    fn get_selected_object(&self) -> Option<SampleObject> {
+    fn get_selected_object(&self) -> Option<ObjectInfoResponse> {
         self.selected_object.clone()
     }
     fn get_server_info(&self) -> ServerInfo {
@@ -137,13 +250,13 @@ impl ServerConnection for ServerClient {
 struct NetworkRequest {}
 
 impl NetworkRequest {
-    fn request(addr: Url, payload: Option<String>) -> String {
+    fn request(addr: Url, payload: Option<String>, credentials: Credentials) -> String {
         let client = Client::new();
 
         let mut headers = Headers::new();
         headers.set(Authorization(Basic {
-                                      username: USERNAME.to_owned(),
-                                      password: None,
+                                      username: credentials.username,
+                                      password: Some(credentials.password),
                                   }));
 
         let payload = payload.unwrap_or("".to_owned());
@@ -156,19 +269,24 @@ impl NetworkRequest {
         result_string
     }
 
-    fn select_object(name: String, df: Arc<Mutex<bool>>, addr: Url) -> SampleObject {
-        println!("{:?}",
-                 json::encode(&ObjectInfoRequest { name: name.clone() }).unwrap());
-        let data = NetworkRequest::request(addr,
-                                           Some(json::encode(&ObjectInfoRequest { name: name })
-                                                    .unwrap()));
+    fn select_object(id: ObjectId,
+                     df: Arc<Mutex<bool>>,
+                     addr: Url,
+                     credentials: Credentials)
+                     -> ObjectInfoResponse {
+        let payload = json::encode(&ObjectIdRequest { id: id }).unwrap();
+        println!("{:?}", payload);
+        let data = NetworkRequest::request(addr, Some(payload), credentials);
         *df.lock().unwrap() = true;
         println!("Object selected");
-        json::decode::<SampleObject>(&data).unwrap()
+        json::decode::<ObjectInfoResponse>(&data).unwrap()
     }
 
-    fn update_objects(df: Arc<Mutex<bool>>, addr: Url) -> HashMap<String, ObjectResponse> {
-        let data = NetworkRequest::request(addr, None);
+    fn update_objects(df: Arc<Mutex<bool>>,
+                      addr: Url,
+                      credentials: Credentials)
+                      -> HashMap<ObjectId, ObjectResponse> {
+        let data = NetworkRequest::request(addr, None, credentials);
 
         let parsed_objects: Vec<ObjectResponse> = match json::decode(&data) {
             Err(e) => {
@@ -179,14 +297,41 @@ impl NetworkRequest {
         };
         let mut objects = HashMap::new();
         for object in parsed_objects {
-            objects.insert(object.name.clone(), object);
+            objects.insert(object.id, object);
         }
         *df.lock().unwrap() = true;
         objects
     }
 
-    fn update_server_info(df: Arc<Mutex<bool>>, addr: Url) -> ServerInfo {
-        let data = NetworkRequest::request(addr, None);
+    // Держит long-poll подписку, пока жив получатель
+    fn stream_updates(sender: Sender<UpdatesResponse>, addr: Url, credentials: Credentials) {
+        let mut since = None;
+        loop {
+            let payload = json::encode(&UpdatesRequest { since: since }).unwrap();
+            let data = NetworkRequest::request(addr.clone(), Some(payload), credentials.clone());
+            let response: UpdatesResponse = match json::decode(&data) {
+                Err(e) => {
+                    println!("Json parsing error: {:?}", e);
+                    since = None;
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+                Ok(data) => data,
+            };
+            since = Some(response.revision);
+            // Пустой ответ - таймаут или занятый сервер, не повторяем запрос сразу
+            if !response.reset && response.updates.is_empty() {
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+            if sender.send(response).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn update_server_info(df: Arc<Mutex<bool>>, addr: Url, credentials: Credentials) -> ServerInfo {
+        let data = NetworkRequest::request(addr, None, credentials);
         let parsed_info: ServerInfo = match json::decode(&data) {
             Err(e) => {
                 println!("Json parsing error: {:?}", e);
@@ -194,6 +339,9 @@ impl NetworkRequest {
                     name: "ErrorName".to_owned(),
                     status: "ErrorStatus".to_owned(),
                     tps: 0,
+                    tick: 0,
+                    api_version: 0,
+                    api_versions: vec![],
                 }
             }
             Ok(data) => data,
@@ -202,8 +350,8 @@ impl NetworkRequest {
         parsed_info
     }
 
-    fn check_server_info(addr: Url) -> ServerInfo {
-        let data = NetworkRequest::request(addr, None);
+    fn check_server_info(addr: Url, credentials: Credentials) -> ServerInfo {
+        let data = NetworkRequest::request(addr, None, credentials);
         match json::decode(&data) {
             Err(_) => panic!("Server not found"),
             Ok(data) => data,
@@ -5,81 +5,87 @@ use std::thread::spawn;
 use network::ServerConnection;
 use server::GameEngine;
 use server::network;
-use data_types::{SampleObject, ObjectResponse, ServerInfo};
+use server::config::GameConfig;
+use server::damage::DamageTable;
+use server::units::UnitRegistry;
+use data_types::{ObjectInfoResponse, ObjectId, ObjectResponse, ServerInfo};
 use level_generator::generate;
+use rand::random;
 
 pub struct ServerManager {
-    engine_timer: f64,
     tps_timer: f64,
 
     tps: u16,
-    selected_object: Option<String>,
+    selected_object: Option<ObjectId>,
     engine: Arc<Mutex<GameEngine>>,
 }
 
 impl ServerManager {
-    pub fn new(width: f64, height: f64, players: Vec<String>) -> Self {
-        let engine = Arc::new(Mutex::new(GameEngine::new(width, height)));
+    pub fn new(width: f64, height: f64, players: Vec<String>) -> Result<Self, String> {
+        let config = GameConfig::new("config/engine.json")?;
+        if players.len() > config.max_players {
+            return Err(format!("Too many players: {}, max_players is {}",
+                               players.len(),
+                               config.max_players));
+        }
+        let damage = DamageTable::new(&config.damage_table)?;
+        let units = UnitRegistry::new(&config.units_dir, &damage)?;
+        let engine =
+            Arc::new(Mutex::new(GameEngine::new(width, height, config, damage, units, random())));
 
         generate(engine.clone(), width, height, players);
         let cloned_engine = engine.clone();
-        spawn(move || network::start(cloned_engine));
+        spawn(move || if let Err(e) = network::start(cloned_engine) {
+                  println!("{}", e);
+              });
 
-        ServerManager {
-            engine_timer: 0.0,
-            tps_timer: 0.0,
-            tps: 0,
-            selected_object: None,
-            engine: engine,
-        }
+        Ok(ServerManager {
+               tps_timer: 0.0,
+               tps: 0,
+               selected_object: None,
+               engine: engine,
+           })
     }
 }
 
 impl ServerConnection for ServerManager {
     fn update(&mut self, elapsed: f64) {
-        self.engine_timer += elapsed;
         self.tps_timer += elapsed;
-        // 60 TPS
-        if self.engine_timer >= 1.0 / 60.0 {
-            self.engine_timer = 0.0;
-            self.tps += 1;
-
-            let mut engine = self.engine.lock().unwrap();
 
-            if self.tps_timer >= 1.0 {
-                engine.update_tps(self.tps);
-                self.tps = 0;
-            }
-
-            engine.game_loop(elapsed);
+        let mut engine = self.engine.lock().unwrap();
+        self.tps += engine.update(elapsed);
 
+        if self.tps_timer >= 1.0 {
+            self.tps_timer -= 1.0;
+            engine.update_tps(self.tps);
+            self.tps = 0;
         }
     }
     fn check_connection(&self) -> Option<ServerInfo> {
         Some(self.engine.lock().unwrap().get_server_info())
     }
-    fn get_objects(&self) -> HashMap<String, ObjectResponse> {
+    fn get_objects(&self) -> HashMap<ObjectId, ObjectResponse> {
         self.engine.lock().unwrap().get_objects()
     }
-    fn select_object(&mut self, name: String) {
-        self.selected_object = Some(name);
+    fn objects_at(&self, x: f64, y: f64, radius: f64) -> Vec<ObjectResponse> {
+        self.engine
+            .lock()
+            .unwrap()
+            .objects_in_radius(x, y, radius)
+            .iter()
+            .map(|obj| obj.to_response())
+            .collect()
+    }
+    fn select_object(&mut self, id: ObjectId) {
+        self.selected_object = Some(id);
     }
-    fn get_selected_object(&self) -> Option<SampleObject> {
-        if let Some(ref name) = self.selected_object {
-            if let Some(obj) = self.engine.lock().unwrap().get_object(&name, None) {
-                Some(obj.clone())
-            } else {
-                None
-            }
-        } else {
-            None
+    fn get_selected_object(&self) -> Option<ObjectInfoResponse> {
+        match self.selected_object {
+            Some(id) => self.engine.lock().unwrap().object_info(id),
+            None => None,
         }
     }
     fn get_server_info(&self) -> ServerInfo {
-        ServerInfo {
-            name: "ServerName".to_owned(),
-            status: "SomeStatus".to_owned(),
-            tps: 0,
-        }
+        self.engine.lock().unwrap().get_server_info()
     }
 }
@@ -8,7 +8,7 @@ use conrod::{Ui, Colorable};
 use ui as UI;
 
 use network::ServerConnection;
-use data_types::{ObjectResponse, ObjectType};
+use data_types::ObjectResponse;
 use utils::camera::{Camera, Direction};
 
 const SPRITE_SIZE: f64 = 10.0;
@@ -77,11 +77,12 @@ impl Scene for GameScene {
 
             // draw objects
             for (_, obj) in self.server.get_objects().iter() {
-                let rectangle = match obj.otype {
-                    ObjectType::Asteroid => Rectangle::new([0.2, 0.2, 0.2, 1.0]),
-                    ObjectType::Builder => Rectangle::new([0.2, 0.8, 0.2, 1.0]),
-                    ObjectType::Harvester => Rectangle::new([0.2, 0.2, 0.8, 1.0]),
-                    ObjectType::Battlecruiser => Rectangle::new([0.8, 0.2, 0.2, 1.0]),
+                let rectangle = match obj.otype.0.as_str() {
+                    "Asteroid" => Rectangle::new([0.2, 0.2, 0.2, 1.0]),
+                    "Builder" => Rectangle::new([0.2, 0.8, 0.2, 1.0]),
+                    "Harvester" => Rectangle::new([0.2, 0.2, 0.8, 1.0]),
+                    "Battlecruiser" => Rectangle::new([0.8, 0.2, 0.2, 1.0]),
+                    _ => Rectangle::new([0.6, 0.6, 0.6, 1.0]),
                 };
                 rectangle.draw(self.camera
                                    .transform([obj.x - (SPRITE_SIZE / 2.0),
@@ -125,7 +126,9 @@ impl Scene for GameScene {
             .set(self.ids.text, &mut ui);
 
         if let Some(ref object) = self.selected_object {
-            widget::Text::new(&format!("name: {}\nowner: {}\ntype: {:?}\npos_x: {}\npos_y: {}",
+            widget::Text::new(&format!("id: {}\nname: {}\nowner: {}\ntype: {:?}\npos_x: {}\n\
+                                        pos_y: {}",
+                                      object.id,
                                       object.name,
                                       object.owner,
                                       object.otype,
@@ -147,20 +150,16 @@ impl Scene for GameScene {
             Input::Press(Keyboard(Key::Left)) => self.camera.shift(Direction::Left, 1.0),
             Input::Press(Keyboard(Key::Right)) => self.camera.shift(Direction::Right, 1.0),
             Input::Press(Mouse(MouseButton::Left)) => {
-                let objects = self.server.get_objects();
-                if let Some((_, object)) =
-                    objects
-                        .iter()
-                        .find(|&(_, obj)| {
-                            intersect(self.camera
-                                          .transform_reverse([self.cursor_pos[0] -
-                                                              SPRITE_SIZE / 2.0,
-                                                              self.cursor_pos[1] -
-                                                              SPRITE_SIZE / 2.0,
-                                                              SPRITE_SIZE,
-                                                              SPRITE_SIZE]),
-                                      [obj.x, obj.y])
-                        }) {
+                let area = self.camera
+                    .transform_reverse([self.cursor_pos[0] - SPRITE_SIZE / 2.0,
+                                        self.cursor_pos[1] - SPRITE_SIZE / 2.0,
+                                        SPRITE_SIZE,
+                                        SPRITE_SIZE]);
+                // Круг, описанный вокруг области клика
+                let radius = (area[2].powf(2.0) + area[3].powf(2.0)).sqrt() / 2.0;
+                let objects = self.server
+                    .objects_at(area[0] + area[2] / 2.0, area[1] + area[3] / 2.0, radius);
+                if let Some(object) = objects.iter().find(|obj| intersect(area, [obj.x, obj.y])) {
                     self.selected_object = Some(object.clone());
                 }
             }
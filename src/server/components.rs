@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+
+use data_types::*;
+
+// Компоненты одного вида по номерам объектов. Обход идёт в порядке номеров,
+// поэтому системы детерминированы без дополнительной сортировки
+pub struct ComponentStore<T> {
+    items: BTreeMap<ObjectId, Vec<T>>,
+}
+
+impl<T> ComponentStore<T> {
+    pub fn new() -> Self {
+        ComponentStore { items: BTreeMap::new() }
+    }
+
+    pub fn insert(&mut self, id: ObjectId, component: T) {
+        self.items.entry(id).or_insert_with(Vec::new).push(component);
+    }
+
+    pub fn remove(&mut self, id: ObjectId) {
+        self.items.remove(&id);
+    }
+
+    pub fn get(&self, id: ObjectId) -> &[T] {
+        match self.items.get(&id) {
+            Some(items) => items,
+            None => &[],
+        }
+    }
+
+    pub fn get_mut(&mut self, id: ObjectId) -> &mut [T] {
+        match self.items.get_mut(&id) {
+            Some(items) => items,
+            None => &mut [],
+        }
+    }
+
+    pub fn first(&self, id: ObjectId) -> Option<&T> {
+        self.get(id).first()
+    }
+
+    pub fn first_mut(&mut self, id: ObjectId) -> Option<&mut T> {
+        self.get_mut(id).first_mut()
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<ObjectId, Vec<T>> {
+        self.items.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> btree_map::IterMut<ObjectId, Vec<T>> {
+        self.items.iter_mut()
+    }
+}
+
+// Все компоненты мира. Новый вид компонента - это новое хранилище здесь
+// и ветка в add и remove
+pub struct Components {
+    pub drives: ComponentStore<Drive>,
+    pub radars: ComponentStore<Radar>,
+    pub weapons: ComponentStore<Weapon>,
+    pub cargo: ComponentStore<Cargo>,
+    pub shells: ComponentStore<Shell>,
+    pub batteries: ComponentStore<Battery>,
+}
+
+impl Components {
+    pub fn new() -> Self {
+        Components {
+            drives: ComponentStore::new(),
+            radars: ComponentStore::new(),
+            weapons: ComponentStore::new(),
+            cargo: ComponentStore::new(),
+            shells: ComponentStore::new(),
+            batteries: ComponentStore::new(),
+        }
+    }
+
+    pub fn add(&mut self, id: ObjectId, components: &[Component]) {
+        for component in components.iter().cloned() {
+            match component {
+                Component::Drive(drive) => self.drives.insert(id, drive),
+                Component::Radar(radar) => self.radars.insert(id, radar),
+                Component::Weapon(weapon) => self.weapons.insert(id, weapon),
+                Component::Cargo(cargo) => self.cargo.insert(id, cargo),
+                Component::Shell(shell) => self.shells.insert(id, shell),
+                Component::Battery(battery) => self.batteries.insert(id, battery),
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: ObjectId) {
+        self.drives.remove(id);
+        self.radars.remove(id);
+        self.weapons.remove(id);
+        self.cargo.remove(id);
+        self.shells.remove(id);
+        self.batteries.remove(id);
+    }
+
+    pub fn info(&self, object: &SampleObject) -> ObjectInfoResponse {
+        ObjectInfoResponse {
+            object: object.clone(),
+            drives: self.drives.get(object.id).to_vec(),
+            radars: self.radars.get(object.id).to_vec(),
+            weapons: self.weapons.get(object.id).to_vec(),
+            cargo: self.cargo.get(object.id).to_vec(),
+            shells: self.shells.get(object.id).to_vec(),
+            batteries: self.batteries.get(object.id).to_vec(),
+        }
+    }
+
+    pub fn can_move(&self, id: ObjectId) -> bool {
+        self.drives.first(id).map_or(false, |drive| drive.can_move())
+    }
+
+    // Трюм для руды, если он есть
+    pub fn mining_cargo(&mut self, id: ObjectId) -> Option<&mut Cargo> {
+        self.cargo
+            .get_mut(id)
+            .iter_mut()
+            .find(|cargo| cargo.ctype == CargoType::Mining)
+    }
+
+    pub fn has_mining_cargo(&self, id: ObjectId) -> bool {
+        self.cargo
+            .get(id)
+            .iter()
+            .any(|cargo| cargo.ctype == CargoType::Mining)
+    }
+
+    // Суммарный заряд всех батарей объекта
+    pub fn energy(&self, id: ObjectId) -> f64 {
+        self.batteries
+            .get(id)
+            .iter()
+            .map(|battery| battery.current)
+            .sum()
+    }
+
+    // Списывает энергию, если её хватает. Нулевой расход не требует батареи
+    pub fn energy_use(&mut self, id: ObjectId, size: f64) -> bool {
+        if size <= 0.0 {
+            return true;
+        }
+        if self.energy(id) < size {
+            return false;
+        }
+        let mut left = size;
+        for battery in self.batteries.get_mut(id).iter_mut() {
+            left -= battery.take(left);
+        }
+        true
+    }
+
+    // Заряжает батареи объекта по очереди, возвращает сколько добавлено
+    pub fn energy_add(&mut self, id: ObjectId, size: f64) -> f64 {
+        let mut added = 0.0;
+        for battery in self.batteries.get_mut(id).iter_mut() {
+            added += battery.add(size - added);
+        }
+        added
+    }
+
+    pub fn energy_full(&self, id: ObjectId) -> bool {
+        self.batteries
+            .get(id)
+            .iter()
+            .all(|battery| battery.current >= battery.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_types::*;
+    use super::{ComponentStore, Components};
+
+    fn battery(current: f64, max: f64) -> Component {
+        Component::Battery(Battery {
+                               current: current,
+                               max: max,
+                               generation: 0.0,
+                               charge_radius: 0.0,
+                               charge_rate: 0.0,
+                           })
+    }
+
+    #[test]
+    fn store_keeps_components_by_object() {
+        let mut store = ComponentStore::new();
+        store.insert(2, "second");
+        store.insert(1, "first");
+        store.insert(1, "extra");
+        assert_eq!(store.get(1), &["first", "extra"]);
+        assert_eq!(store.first(2), Some(&"second"));
+        assert!(store.get(3).is_empty());
+        assert_eq!(store.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![1, 2]);
+        store.remove(1);
+        assert!(store.first(1).is_none());
+    }
+
+    #[test]
+    fn add_and_remove_sort_components_by_kind() {
+        let mut components = Components::new();
+        components.add(1,
+                       &[battery(10.0, 10.0),
+                         battery(0.0, 5.0),
+                         Component::Shell(Shell {
+                                              armor: ArmorType("Light".to_owned()),
+                                              health: 100.0,
+                                          })]);
+        assert_eq!(components.batteries.get(1).len(), 2);
+        assert_eq!(components.shells.get(1).len(), 1);
+        assert!(components.drives.get(1).is_empty());
+        assert!(!components.can_move(1));
+
+        components.remove(1);
+        assert!(components.batteries.get(1).is_empty());
+        assert!(components.shells.get(1).is_empty());
+    }
+
+    #[test]
+    fn energy_use_drains_batteries_in_order() {
+        let mut components = Components::new();
+        components.add(1, &[battery(3.0, 10.0), battery(4.0, 10.0)]);
+        assert_eq!(components.energy(1), 7.0);
+
+        assert!(components.energy_use(1, 5.0));
+        assert_eq!(components.batteries.get(1)[0].current, 0.0);
+        assert_eq!(components.batteries.get(1)[1].current, 2.0);
+
+        // Не хватает - ничего не списывается
+        assert!(!components.energy_use(1, 3.0));
+        assert_eq!(components.energy(1), 2.0);
+
+        // Нулевой расход не требует батареи
+        assert!(components.energy_use(2, 0.0));
+        assert!(!components.energy_use(2, 1.0));
+    }
+
+    #[test]
+    fn energy_add_fills_batteries_up_to_max() {
+        let mut components = Components::new();
+        components.add(1, &[battery(8.0, 10.0), battery(0.0, 5.0)]);
+        assert!(!components.energy_full(1));
+
+        assert_eq!(components.energy_add(1, 4.0), 4.0);
+        assert_eq!(components.batteries.get(1)[0].current, 10.0);
+        assert_eq!(components.batteries.get(1)[1].current, 2.0);
+
+        assert_eq!(components.energy_add(1, 10.0), 3.0);
+        assert!(components.energy_full(1));
+        assert_eq!(components.energy_add(2, 1.0), 0.0);
+    }
+}
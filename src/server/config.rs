@@ -1,26 +1,160 @@
 use rustc_serialize::json;
+use sha2::{Digest, Sha256};
+use pbkdf2::pbkdf2_hmac;
+use rand::random;
 use std::fs::File;
 use std::io::Read;
+use std::net::ToSocketAddrs;
+
+// Учётная запись, которой видна вся карта
+pub const ADMIN: &'static str = "admin";
+// Число итераций PBKDF2 для новых паролей
+pub const PASSWORD_ITERATIONS: u32 = 100_000;
 
 #[derive(RustcDecodable)]
 pub struct GameConfig {
     pub servername: String,
+    pub address: String,
+    pub port: u16,
+    pub max_players: usize,
+    pub max_body_size: usize,
+    pub threads: usize,
+    pub commands_per_second: f64,
+    pub max_queued_commands: usize,
+    // Ресурсы каждого игрока в начале игры
+    pub start_resources: f64,
+    // Путь к таблице множителей урона броня x оружие
+    pub damage_table: String,
+    // Каталог с описаниями типов объектов, по файлу на тип
+    pub units_dir: String,
+    pub accounts: Vec<PlayerAccount>,
+}
+
+// Учётная запись игрока, пароль передаётся через Basic-auth. В конфиге
+// хранится только hex(PBKDF2-HMAC-SHA256(password, salt, iterations)),
+// запись для конфига печатает dedicated_server --hash-password
+#[derive(RustcDecodable, RustcEncodable, Clone)]
+pub struct PlayerAccount {
+    pub username: String,
+    pub salt: String,
+    pub iterations: u32,
+    pub password_hash: String,
 }
 
 impl GameConfig {
-    pub fn new(path: &str) -> Self {
+    pub fn new(path: &str) -> Result<Self, String> {
         let mut file = match File::open(path) {
             Ok(data) => data,
-            Err(e) => panic!("Game config file open error: {:?}", e),
+            Err(e) => return Err(format!("Game config file {} open error: {}", path, e)),
         };
         let mut string = String::new();
-        file.read_to_string(&mut string).unwrap();
+        if let Err(e) = file.read_to_string(&mut string) {
+            return Err(format!("Game config file {} read error: {}", path, e));
+        }
 
-        match json::decode(&string) {
-            Err(e) => {
-                panic!("Json parsing error: {:?}", e);
-            }
+        let config: GameConfig = match json::decode(&string) {
+            Err(e) => return Err(format!("Game config {} parsing error: {}", path, e)),
             Ok(data) => data,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("Game config: port must not be 0".to_owned());
+        }
+        if let Err(e) = (self.address.as_str(), self.port).to_socket_addrs() {
+            return Err(format!("Game config: wrong address {}:{} ({})",
+                               self.address,
+                               self.port,
+                               e));
         }
+        if self.max_players == 0 {
+            return Err("Game config: max_players must be greater than 0".to_owned());
+        }
+        if self.max_body_size == 0 {
+            return Err("Game config: max_body_size must be greater than 0".to_owned());
+        }
+        if self.threads == 0 {
+            return Err("Game config: threads must be greater than 0".to_owned());
+        }
+        if !(self.commands_per_second > 0.0) {
+            return Err("Game config: commands_per_second must be greater than 0".to_owned());
+        }
+        if self.max_queued_commands == 0 {
+            return Err("Game config: max_queued_commands must be greater than 0".to_owned());
+        }
+        if !(self.start_resources >= 0.0) {
+            return Err("Game config: start_resources must not be negative".to_owned());
+        }
+        for (i, account) in self.accounts.iter().enumerate() {
+            if account.username.is_empty() || account.salt.is_empty() {
+                return Err(format!("Game config: account #{} has empty username or salt", i));
+            }
+            if account.iterations == 0 {
+                return Err(format!("Game config: account {} has 0 iterations",
+                                   account.username));
+            }
+            if account.password_hash.len() != 64 ||
+               !account.password_hash.chars().all(|c| c.is_digit(16)) {
+                return Err(format!("Game config: account {} has malformed password_hash",
+                                   account.username));
+            }
+            if self.accounts[..i]
+                   .iter()
+                   .any(|other| other.username == account.username) {
+                return Err(format!("Game config: duplicate account {}", account.username));
+            }
+        }
+        Ok(())
     }
 }
+
+impl PlayerAccount {
+    // Учётная запись со случайной солью
+    pub fn new(username: &str, password: &str) -> Self {
+        let salt = format!("{:016x}{:016x}", random::<u64>(), random::<u64>());
+        PlayerAccount {
+            username: username.to_owned(),
+            password_hash: PlayerAccount::hash_password(&salt, password, PASSWORD_ITERATIONS),
+            salt: salt,
+            iterations: PASSWORD_ITERATIONS,
+        }
+    }
+
+    pub fn hash_password(salt: &str, password: &str, iterations: u32) -> String {
+        let mut hash = [0u8; 32];
+        pbkdf2_hmac::<Sha256>(password.as_bytes(), salt.as_bytes(), iterations, &mut hash);
+        to_hex(&hash)
+    }
+
+    pub fn check_password(&self, password: &str) -> bool {
+        let actual = PlayerAccount::hash_password(&self.salt, password, self.iterations);
+        constant_time_eq(&self.password_hash.to_lowercase(), &actual)
+    }
+
+    // Быстрый отпечаток пароля, чтобы не считать PBKDF2 на каждый запрос
+    // с уже проверенным паролем. Хранится только в памяти
+    pub fn fingerprint(&self, password: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update(password.as_bytes());
+        to_hex(&hasher.finalize())
+    }
+}
+
+// Сравнение за постоянное время
+pub fn constant_time_eq(expected: &str, actual: &str) -> bool {
+    if expected.len() != actual.len() {
+        return false;
+    }
+    expected
+        .bytes()
+        .zip(actual.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,136 @@
+use rustc_serialize::json;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+
+use data_types::{ArmorType, WeaponType};
+
+// Множители урона: класс брони -> класс оружия -> множитель.
+// Новый класс брони или оружия добавляется только в файл таблицы
+pub struct DamageTable {
+    multipliers: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+impl DamageTable {
+    pub fn new(path: &str) -> Result<Self, String> {
+        let mut file = match File::open(path) {
+            Ok(data) => data,
+            Err(e) => return Err(format!("Damage table file {} open error: {}", path, e)),
+        };
+        let mut string = String::new();
+        if let Err(e) = file.read_to_string(&mut string) {
+            return Err(format!("Damage table file {} read error: {}", path, e));
+        }
+
+        let multipliers = match json::decode(&string) {
+            Err(e) => return Err(format!("Damage table {} parsing error: {}", path, e)),
+            Ok(data) => data,
+        };
+        let table = DamageTable { multipliers: multipliers };
+        table.validate()?;
+        Ok(table)
+    }
+
+    // Каждый класс брони должен задавать множитель для каждого класса оружия,
+    // упомянутого в таблице
+    pub fn validate(&self) -> Result<(), String> {
+        if self.multipliers.is_empty() {
+            return Err("Damage table: no armor classes".to_owned());
+        }
+        let weapons = self.weapons();
+        if weapons.is_empty() {
+            return Err("Damage table: no weapon classes".to_owned());
+        }
+        for (armor, row) in self.multipliers.iter() {
+            for weapon in weapons.iter() {
+                match row.get(*weapon) {
+                    Some(multiplier) => {
+                        if !(*multiplier >= 0.0) || multiplier.is_infinite() {
+                            return Err(format!("Damage table: wrong multiplier {} for armor {} \
+                                                and weapon {}",
+                                               multiplier,
+                                               armor,
+                                               weapon));
+                        }
+                    }
+                    None => {
+                        return Err(format!("Damage table: armor {} has no multiplier for \
+                                            weapon {}",
+                                           armor,
+                                           weapon))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_armor(&self, armor: &ArmorType) -> bool {
+        self.multipliers.contains_key(&armor.0)
+    }
+
+    pub fn has_weapon(&self, weapon: &WeaponType) -> bool {
+        self.weapons().contains(&&weapon.0)
+    }
+
+    // Неизвестные классы и отсутствие оружия урона не наносят
+    pub fn multiplier(&self, armor: &ArmorType, weapon: &WeaponType) -> f64 {
+        self.multipliers
+            .get(&armor.0)
+            .and_then(|row| row.get(&weapon.0))
+            .cloned()
+            .unwrap_or(0.0)
+    }
+
+    fn weapons(&self) -> Vec<&String> {
+        let mut weapons: Vec<&String> = self.multipliers
+            .values()
+            .flat_map(|row| row.keys())
+            .collect();
+        weapons.sort();
+        weapons.dedup();
+        weapons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_serialize::json;
+    use data_types::{ArmorType, WeaponType};
+    use super::DamageTable;
+
+    fn table(source: &str) -> DamageTable {
+        DamageTable { multipliers: json::decode(source).unwrap() }
+    }
+
+    #[test]
+    fn validate_accepts_full_table() {
+        let table = table(r#"{ "Heavy": { "Laser": 0.5, "Mining": 0.0 },
+                               "Light": { "Laser": 1.0, "Mining": 0.1 } }"#);
+        assert!(table.validate().is_ok());
+        let heavy = ArmorType("Heavy".to_owned());
+        assert_eq!(table.multiplier(&heavy, &WeaponType("Laser".to_owned())), 0.5);
+        assert_eq!(table.multiplier(&heavy, &WeaponType("Plasma".to_owned())), 0.0);
+        assert!(table.has_weapon(&WeaponType("Mining".to_owned())));
+    }
+
+    #[test]
+    fn validate_rejects_empty_table() {
+        assert!(table("{}").validate().is_err());
+        assert!(table(r#"{ "Heavy": {} }"#).validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_missing_multiplier() {
+        let table = table(r#"{ "Heavy": { "Laser": 0.5 },
+                               "Light": { "Laser": 1.0, "Mining": 0.1 } }"#);
+        let error = table.validate().unwrap_err();
+        assert!(error.contains("Heavy") && error.contains("Mining"));
+    }
+
+    #[test]
+    fn validate_rejects_negative_multiplier() {
+        let table = table(r#"{ "Heavy": { "Laser": -1.0 } }"#);
+        assert!(table.validate().is_err());
+    }
+}
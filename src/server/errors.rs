@@ -0,0 +1,135 @@
+use iron::prelude::*;
+use iron::status;
+use iron::headers::ContentType;
+use rustc_serialize::json;
+use data_types::{ErrorResponse, ObjectId, ObjectType};
+use server::events::CommandId;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServerError {
+    MalformedJson(String),
+    ObjectNotFound(ObjectId),
+    NotOwner(ObjectId),
+    InsufficientEnergy(ObjectId),
+    NoDrive(ObjectId),
+    NoWeapon(ObjectId),
+    OutOfRange(ObjectId),
+    NoMiningCargo(ObjectId),
+    NoBuilderNearby(ObjectId),
+    CantBuild(ObjectId, ObjectType),
+    UnknownObjectType(ObjectType),
+    InsufficientResources(String),
+    CommandNotFound(CommandId),
+    PayloadTooLarge(usize),
+    RateLimited(String),
+    Unauthorized,
+    Forbidden,
+    RouteNotFound(String),
+    MethodNotAllowed(String),
+}
+
+impl ServerError {
+    pub fn status(&self) -> status::Status {
+        match *self {
+            ServerError::MalformedJson(_) => status::BadRequest,
+            ServerError::InsufficientEnergy(_) => status::BadRequest,
+            ServerError::NoDrive(_) => status::BadRequest,
+            ServerError::NoWeapon(_) => status::BadRequest,
+            ServerError::OutOfRange(_) => status::BadRequest,
+            ServerError::NoMiningCargo(_) => status::BadRequest,
+            ServerError::NoBuilderNearby(_) => status::BadRequest,
+            ServerError::CantBuild(_, _) => status::BadRequest,
+            ServerError::UnknownObjectType(_) => status::BadRequest,
+            ServerError::InsufficientResources(_) => status::BadRequest,
+            ServerError::Unauthorized => status::Unauthorized,
+            ServerError::NotOwner(_) => status::Forbidden,
+            ServerError::Forbidden => status::Forbidden,
+            ServerError::ObjectNotFound(_) => status::NotFound,
+            ServerError::CommandNotFound(_) => status::NotFound,
+            ServerError::PayloadTooLarge(_) => status::PayloadTooLarge,
+            ServerError::RateLimited(_) => status::TooManyRequests,
+            ServerError::RouteNotFound(_) => status::NotFound,
+            ServerError::MethodNotAllowed(_) => status::MethodNotAllowed,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            ServerError::MalformedJson(_) => "MalformedJson",
+            ServerError::ObjectNotFound(_) => "ObjectNotFound",
+            ServerError::NotOwner(_) => "NotOwner",
+            ServerError::InsufficientEnergy(_) => "InsufficientEnergy",
+            ServerError::NoDrive(_) => "NoDrive",
+            ServerError::NoWeapon(_) => "NoWeapon",
+            ServerError::OutOfRange(_) => "OutOfRange",
+            ServerError::NoMiningCargo(_) => "NoMiningCargo",
+            ServerError::NoBuilderNearby(_) => "NoBuilderNearby",
+            ServerError::CantBuild(_, _) => "CantBuild",
+            ServerError::UnknownObjectType(_) => "UnknownObjectType",
+            ServerError::InsufficientResources(_) => "InsufficientResources",
+            ServerError::CommandNotFound(_) => "CommandNotFound",
+            ServerError::PayloadTooLarge(_) => "PayloadTooLarge",
+            ServerError::RateLimited(_) => "RateLimited",
+            ServerError::Unauthorized => "Unauthorized",
+            ServerError::Forbidden => "Forbidden",
+            ServerError::RouteNotFound(_) => "RouteNotFound",
+            ServerError::MethodNotAllowed(_) => "MethodNotAllowed",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match *self {
+            ServerError::MalformedJson(ref e) => format!("Json parsing error: {}", e),
+            ServerError::ObjectNotFound(id) => format!("Object {} not found", id),
+            ServerError::NotOwner(id) => format!("Object {} belongs to another player", id),
+            ServerError::InsufficientEnergy(id) => format!("Object {} has not enough energy", id),
+            ServerError::NoDrive(id) => format!("Object {} can't move", id),
+            ServerError::NoWeapon(id) => format!("Object {} has no suitable weapon", id),
+            ServerError::OutOfRange(id) => format!("Object {} can't reach the target", id),
+            ServerError::NoMiningCargo(id) => format!("Object {} can't carry ore", id),
+            ServerError::NoBuilderNearby(id) => {
+                format!("Object {} has no friendly Builder nearby", id)
+            }
+            ServerError::CantBuild(id, ref otype) => {
+                format!("Object {} can't build {}", id, otype.to_string())
+            }
+            ServerError::UnknownObjectType(ref otype) => {
+                format!("Unknown object type {}", otype.to_string())
+            }
+            ServerError::InsufficientResources(ref player) => {
+                format!("Player {} has not enough resources", player)
+            }
+            ServerError::CommandNotFound(id) => format!("Command {} not found", id),
+            ServerError::PayloadTooLarge(limit) => {
+                format!("Request body is larger than {} bytes", limit)
+            }
+            ServerError::RateLimited(ref reason) => format!("Rate limit exceeded: {}", reason),
+            ServerError::Unauthorized => "Wrong username or password".to_owned(),
+            ServerError::Forbidden => "Access denied".to_owned(),
+            ServerError::RouteNotFound(ref path) => format!("Route {} not found", path),
+            ServerError::MethodNotAllowed(ref method) => {
+                format!("Method {} is not allowed for this route", method)
+            }
+        }
+    }
+
+    pub fn to_error_response(&self) -> ErrorResponse {
+        ErrorResponse {
+            error: self.kind().to_owned(),
+            message: self.message(),
+        }
+    }
+
+    pub fn to_response(&self) -> Response {
+        let body = self.to_error_response();
+        let mut response = Response::with((self.status(), json::encode(&body).unwrap()));
+        response.headers.set(ContentType::json());
+        if *self == ServerError::Unauthorized {
+            response
+                .headers
+                .set_raw("WWW-Authenticate",
+                         vec![b"Basic realm=\"complex_crystals\"".to_vec()]);
+        }
+        response
+    }
+}
@@ -1,45 +1,65 @@
-use data_types::{ObjectType, WeaponType};
+use data_types::{ObjectId, Order, WeaponType};
+use server::errors::ServerError;
 
-pub enum Event {
-    MoveRequest(NetworkMoveEvent),
-    FireRequest(NetworkFireEvent),
-    BuildRequest(NetworkBuildEvent),
-    Move(MoveEvent),
-    Destroy(DestroyEvent),
-    Damage(DamageEvent),
-    Build(BuildEvent),
+pub type CommandId = u64;
+
+#[derive(Clone)]
+pub enum CommandState {
+    Queued,
+    Rejected(ServerError),
+    InProgress,
+    Completed,
+    // Приказ снят /cancel или заменён новым приказом
+    Cancelled,
 }
 
-pub struct NetworkMoveEvent {
-    pub name: String,
+pub struct Command {
     pub owner: String,
-    pub dest_x: f64,
-    pub dest_y: f64,
+    pub state: CommandState,
+    // Объект, построенный по команде
+    pub object: Option<ObjectId>,
 }
 
-pub struct NetworkFireEvent {
-    pub name: String,
-    pub owner: String,
-    pub dest_x: f64,
-    pub dest_y: f64,
+impl CommandState {
+    // Команда ещё не завершена
+    pub fn is_active(&self) -> bool {
+        match *self {
+            CommandState::Queued | CommandState::InProgress => true,
+            CommandState::Rejected(_) |
+            CommandState::Completed |
+            CommandState::Cancelled => false,
+        }
+    }
+}
+
+pub enum Event {
+    OrderRequest(NetworkOrderEvent),
+    CancelRequest(NetworkCancelEvent),
+    WeaponStopRequest(NetworkCancelEvent),
+    Destroy(DestroyEvent),
+    Damage(DamageEvent),
+    // Приказы из /batch, обрабатываются все за один тик
+    Batch(Vec<Event>),
 }
 
-pub struct NetworkBuildEvent {
-    pub name: String,
+// append - поставить приказ в конец очереди объекта, иначе очередь заменяется
+pub struct NetworkOrderEvent {
+    pub command: CommandId,
+    pub id: ObjectId,
     pub owner: String,
-    pub b_type: ObjectType,
-    pub b_name: String,
+    pub order: Order,
+    pub append: bool,
 }
 
-#[derive(Clone)]
-pub struct MoveEvent {
-    pub name: String,
-    pub dest_x: f64,
-    pub dest_y: f64,
+// Приказ без параметров: отмена приказов или остановка оружия
+pub struct NetworkCancelEvent {
+    pub command: CommandId,
+    pub id: ObjectId,
+    pub owner: String,
 }
 
 pub struct DestroyEvent {
-    pub name: String,
+    pub id: ObjectId,
 }
 
 pub struct DamageEvent {
@@ -48,14 +68,6 @@ pub struct DamageEvent {
     pub size: f64,
     pub d_type: WeaponType,
     pub damage: f64,
-}
-
-#[derive(Clone)]
-pub struct BuildEvent {
-    pub name: String,
-    pub b_name: String,
-    pub b_type: ObjectType,
-    pub speed: f64,
-    pub progress: f64,
-    pub max_progress: f64,
+    // Кто стрелял, ему достаётся добытая руда
+    pub source: Option<ObjectId>,
 }
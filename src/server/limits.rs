@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use server::errors::ServerError;
+use data_types::PlayerLimitsResponse;
+
+struct PlayerCounters {
+    tokens: f64,
+    last_refill: Instant,
+    pending: usize,
+    accepted: u64,
+    limited: u64,
+}
+
+// Ограничение приказов игрока: не больше commands_per_second приказов в секунду
+// (с запасом на секунду вперёд, поэтому /batch не длиннее commands_per_second)
+// и не больше max_pending приказов, ждущих
+// обработки в очереди событий движка. Отмена и остановка оружия в max_pending
+// не считаются, чтобы игрок всегда мог остановить свои объекты
+pub struct RateLimiter {
+    commands_per_second: f64,
+    max_pending: usize,
+    players: HashMap<String, PlayerCounters>,
+}
+
+impl RateLimiter {
+    pub fn new(commands_per_second: f64, max_pending: usize) -> Self {
+        RateLimiter {
+            commands_per_second: commands_per_second,
+            max_pending: max_pending,
+            players: HashMap::new(),
+        }
+    }
+
+    // Списывает count приказов и занимает queued мест в очереди
+    // событий или возвращает ошибку, ничего не списав
+    pub fn acquire(&mut self,
+                   player: &String,
+                   count: usize,
+                   queued: usize)
+                   -> Result<(), ServerError> {
+        let rate = self.commands_per_second;
+        let max_pending = self.max_pending;
+        let counters = self.counters(player);
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(counters.last_refill);
+        let elapsed = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9;
+        counters.tokens = (counters.tokens + elapsed * rate).min(rate);
+        counters.last_refill = now;
+
+        if counters.pending + queued > max_pending {
+            counters.limited += count as u64;
+            return Err(ServerError::RateLimited(format!("More than {} queued commands",
+                                                        max_pending)));
+        }
+        if count as f64 > rate {
+            counters.limited += count as u64;
+            return Err(ServerError::RateLimited(format!("More than {} commands in one batch",
+                                                        rate)));
+        }
+        if counters.tokens < count as f64 {
+            counters.limited += count as u64;
+            return Err(ServerError::RateLimited(format!("More than {} commands per second",
+                                                        rate)));
+        }
+        counters.tokens -= count as f64;
+        counters.pending += queued;
+        counters.accepted += count as u64;
+        Ok(())
+    }
+
+    // Движок обработал событие приказа, занятое им место освобождается
+    pub fn command_handled(&mut self, player: &String) {
+        let counters = self.counters(player);
+        if counters.pending > 0 {
+            counters.pending -= 1;
+        }
+    }
+
+    pub fn stats(&self) -> Vec<PlayerLimitsResponse> {
+        self.players
+            .iter()
+            .map(|(player, counters)| {
+                     PlayerLimitsResponse {
+                         player: player.clone(),
+                         tokens: counters.tokens,
+                         pending: counters.pending,
+                         accepted: counters.accepted,
+                         limited: counters.limited,
+                     }
+                 })
+            .collect()
+    }
+
+    fn counters(&mut self, player: &String) -> &mut PlayerCounters {
+        let rate = self.commands_per_second;
+        self.players
+            .entry(player.clone())
+            .or_insert_with(|| {
+                                PlayerCounters {
+                                    tokens: rate,
+                                    last_refill: Instant::now(),
+                                    pending: 0,
+                                    accepted: 0,
+                                    limited: 0,
+                                }
+                            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimiter;
+
+    #[test]
+    fn acquire_spends_one_token_per_command() {
+        let player = "Player0".to_owned();
+        let mut limits = RateLimiter::new(5.0, 100);
+        assert!(limits.acquire(&player, 1, 1).is_ok());
+        // Пакет платит за каждый приказ
+        assert!(limits.acquire(&player, 3, 3).is_ok());
+        assert!(limits.acquire(&player, 2, 2).is_err());
+        assert!(limits.acquire(&player, 1, 1).is_ok());
+        assert!(limits.acquire(&player, 1, 1).is_err());
+
+        let stats = limits.stats();
+        assert_eq!(stats[0].accepted, 5);
+        assert_eq!(stats[0].limited, 3);
+    }
+
+    #[test]
+    fn acquire_rejects_batch_longer_than_rate() {
+        let player = "Player0".to_owned();
+        let mut limits = RateLimiter::new(5.0, 100);
+        assert!(limits.acquire(&player, 6, 6).is_err());
+        assert!(limits.acquire(&player, 5, 5).is_ok());
+    }
+
+    #[test]
+    fn acquire_checks_pending_limit() {
+        let player = "Player0".to_owned();
+        let mut limits = RateLimiter::new(100.0, 3);
+        assert!(limits.acquire(&player, 4, 4).is_err());
+        assert!(limits.acquire(&player, 3, 3).is_ok());
+        assert!(limits.acquire(&player, 1, 1).is_err());
+        // Отмена проходит и при заполненной очереди
+        assert!(limits.acquire(&player, 1, 0).is_ok());
+        limits.command_handled(&player);
+        assert!(limits.acquire(&player, 1, 1).is_ok());
+        assert_eq!(limits.stats()[0].pending, 3);
+    }
+
+    #[test]
+    fn players_are_limited_separately() {
+        let first = "Player0".to_owned();
+        let second = "Player1".to_owned();
+        let mut limits = RateLimiter::new(1.0, 100);
+        assert!(limits.acquire(&first, 1, 1).is_ok());
+        assert!(limits.acquire(&first, 1, 1).is_err());
+        assert!(limits.acquire(&second, 1, 1).is_ok());
+    }
+}
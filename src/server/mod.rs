@@ -1,7 +1,16 @@
+pub mod components;
 pub mod config;
+pub mod damage;
+pub mod errors;
 pub mod events;
+pub mod limits;
 pub mod server;
 pub mod network;
 pub mod requests;
+pub mod schema;
+pub mod spatial;
+pub mod units;
+pub mod updates;
+pub mod version;
 
 pub use server::server::GameEngine;
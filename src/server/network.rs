@@ -1,5 +1,10 @@
 use server::GameEngine;
 use server::requests;
+use server::config::{constant_time_eq, PlayerAccount, ADMIN};
+use server::version::{API_VERSIONS, LEGACY_API_VERSION};
+use server::errors::ServerError;
+use server::schema::schema;
+use data_types::*;
 
 use std::sync::{Arc, Mutex};
 use std::io::Read;
@@ -8,137 +13,472 @@ use std::collections::HashMap;
 use iron::prelude::*;
 use iron::Handler;
 use iron::status;
-use iron::headers::{Authorization, Basic};
+use iron::headers::{Authorization, Basic, ContentType};
+use iron::method::Method;
+use iron::typemap::Key;
+use rustc_serialize::{json, Decodable};
+use rustc_serialize::json::Json;
+
+// Логин пользователя, прошедшего проверку в Router
+struct Username;
+
+impl Key for Username {
+    type Value = String;
+}
+
+// Максимальный размер тела запроса из конфига
+struct BodyLimit;
+
+impl Key for BodyLimit {
+    type Value = usize;
+}
+
+// Версия API, по которой пришёл запрос
+pub struct ApiVersion;
+
+impl Key for ApiVersion {
+    type Value = u32;
+}
+
+// Описание маршрута: по нему Router проверяет запрос и строит ответ /api
+struct RouteSpec {
+    path: &'static str,
+    versions: Vec<u32>,
+    methods: Vec<Method>,
+    admin: bool,
+    request: Option<Json>,
+    response: Json,
+}
+
+impl RouteSpec {
+    fn new(path: &'static str) -> Self {
+        RouteSpec {
+            path: path,
+            versions: API_VERSIONS.to_vec(),
+            methods: vec![Method::Get],
+            admin: false,
+            request: None,
+            response: Json::Null,
+        }
+    }
+
+    fn admin(mut self) -> Self {
+        self.admin = true;
+        self
+    }
+
+    // Ограничивает маршрут версиями API, по умолчанию он есть во всех.
+    // Пока формат ни одного маршрута не менялся, поэтому используется только в тестах
+    #[allow(dead_code)]
+    fn versions(mut self, versions: &[u32]) -> Self {
+        self.versions = versions.to_vec();
+        self
+    }
+
+    fn accepts<T: Decodable>(self) -> Self {
+        self.accepts_schema(schema::<T>())
+    }
+
+    fn accepts_schema(mut self, request: Json) -> Self {
+        self.methods = vec![Method::Get, Method::Post];
+        self.request = Some(request);
+        self
+    }
+
+    fn returns<T: Decodable>(mut self) -> Self {
+        self.response = schema::<T>();
+        self
+    }
+
+    fn describe(&self, version: u32) -> ApiRouteResponse {
+        ApiRouteResponse {
+            path: format!("/v{}/{}", version, self.path),
+            version: version,
+            methods: self.methods.iter().map(|method| method.to_string()).collect(),
+            auth: if self.admin { ADMIN } else { "player" }.to_owned(),
+            request: self.request.clone(),
+            response: self.response.clone(),
+        }
+    }
+}
+
+struct Route {
+    spec: RouteSpec,
+    handler: Box<Handler>,
+}
 
 struct Router {
-    routes: HashMap<String, Box<Handler>>,
+    routes: HashMap<String, (u32, Arc<Route>)>,
+    accounts: Vec<PlayerAccount>,
+    // Отпечатки последних подошедших паролей по логинам
+    verified: Mutex<HashMap<String, String>>,
+    max_body_size: usize,
 }
 
 impl Router {
-    fn new() -> Self {
-        Router { routes: HashMap::new() }
+    fn new(accounts: Vec<PlayerAccount>, max_body_size: usize) -> Self {
+        Router {
+            routes: HashMap::new(),
+            accounts: accounts,
+            verified: Mutex::new(HashMap::new()),
+            max_body_size: max_body_size,
+        }
     }
 
-    fn add_route<H>(&mut self, path: String, handler: H)
+    fn add_route<H>(&mut self, spec: RouteSpec, handler: H)
         where H: Handler
     {
-        self.routes.insert(path, Box::new(handler));
+        let route = Arc::new(Route {
+                                 spec: spec,
+                                 handler: Box::new(handler),
+                             });
+        for &version in route.spec.versions.iter() {
+            self.routes
+                .insert(format!("v{}/{}", version, route.spec.path),
+                        (version, route.clone()));
+            if version == LEGACY_API_VERSION {
+                self.routes
+                    .insert(route.spec.path.to_owned(), (version, route.clone()));
+            }
+        }
+    }
+
+    fn describe(&self) -> Vec<ApiRouteResponse> {
+        let mut routes: Vec<ApiRouteResponse> = self.routes
+            .iter()
+            .filter(|&(path, &(_, ref route))| path != route.spec.path)
+            .map(|(_, &(version, ref route))| route.spec.describe(version))
+            .collect();
+        routes.sort_by(|a, b| a.path.cmp(&b.path));
+        routes
     }
 }
 
 impl Handler for Router {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
-        match self.routes.get(&req.url.path().join("/")) {
-            Some(handler) => handler.handle(req),
-            None => Ok(Response::with(status::NotFound)),
+        let username = match authenticate(req, &self.accounts, &self.verified) {
+            Some(username) => username,
+            None => return Ok(ServerError::Unauthorized.to_response()),
+        };
+
+        let path = req.url.path().join("/");
+        let (version, route) = match self.routes.get(&path) {
+            Some(&(version, ref route)) => (version, route),
+            None => return Ok(ServerError::RouteNotFound(path).to_response()),
+        };
+        if !route.spec.methods.contains(&req.method) {
+            return Ok(ServerError::MethodNotAllowed(req.method.to_string()).to_response());
         }
+        if route.spec.admin && username != ADMIN {
+            return Ok(ServerError::Forbidden.to_response());
+        }
+
+        req.extensions.insert::<Username>(username);
+        req.extensions.insert::<BodyLimit>(self.max_body_size);
+        req.extensions.insert::<ApiVersion>(version);
+        route.handler.handle(req)
     }
 }
 
-pub fn start(mutex: Arc<Mutex<GameEngine>>) {
-    let mut router = Router::new();
+// Запускает HTTP сервер и блокирует поток до его остановки
+pub fn start(mutex: Arc<Mutex<GameEngine>>) -> Result<(), String> {
+    let (address, port, threads, mut router) = {
+        let engine = mutex.lock().unwrap();
+        let config = &engine.config;
+        (config.address.clone(),
+         config.port,
+         config.threads,
+         Router::new(config.accounts.clone(), config.max_body_size))
+    };
 
     let cloned_engine = mutex.clone();
-    router.add_route("world_size".to_owned(),
-                     move |_: &mut Request| match requests::world_size(&cloned_engine) {
-                         Some(expr) => Ok(Response::with((status::Ok, expr))),
-                         None => Ok(Response::with((status::Ok))),
-                     });
+    let spec = RouteSpec::new("world_size").returns::<WorldSizeResponse>();
+    router.add_route(spec,
+                     move |_: &mut Request| respond(requests::world_size(&cloned_engine)));
 
     let cloned_engine = mutex.clone();
-    router.add_route("objects".to_string(), move |req: &mut Request| {
-        if !check_username(&req, "admin".to_owned()) {
-            return Ok(Response::with((status::Ok))); // TODO: А вот тут должна быть ошибка
-        }
-        match requests::objects(&cloned_engine) {
-            Some(response) => Ok(Response::with((status::Ok, response))),
-            None => Ok(Response::with((status::Ok))), // TODO: Заменить на ошибку, хотя вряд ли она может тут возникнуть
-        }
+    let spec = RouteSpec::new("objects").returns::<Vec<ObjectResponse>>();
+    router.add_route(spec, move |req: &mut Request| {
+        respond(requests::objects(&cloned_engine, get_username(&req)))
+    });
+
+    let cloned_engine = mutex.clone();
+    let spec = RouteSpec::new("updates")
+        .accepts::<UpdatesRequest>()
+        .returns::<UpdatesResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::updates(&cloned_engine, buf, get_username(&req)));
+        respond(result)
     });
+
     let cloned_engine = mutex.clone();
-    router.add_route("object_info".to_string(), move |req: &mut Request| {
-        let mut buf = String::new();
-        req.body.read_to_string(&mut buf).unwrap();
+    let spec = RouteSpec::new("object_info")
+        .accepts::<ObjectIdRequest>()
+        .returns::<ObjectInfoResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let buf = match read_body(req) {
+            Ok(buf) => buf,
+            Err(e) => return respond(Err(e)),
+        };
         println!("{:?} \n {:?}", req, buf);
 
-        match requests::object_info(&cloned_engine, buf, get_username(&req)) {
-            Some(response) => Ok(Response::with((status::Ok, response))),
-            None => Ok(Response::with((status::Ok))),
-        }
+        respond(requests::object_info(&cloned_engine, buf, get_username(&req)))
     });
 
     let cloned_engine = mutex.clone();
-    router.add_route("move".to_string(), move |req: &mut Request| {
-        let mut buf = String::new();
-        req.body.read_to_string(&mut buf).unwrap();
+    let spec = RouteSpec::new("rename")
+        .accepts::<RenameRequest>()
+        .returns::<ObjectResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::rename(&cloned_engine, buf, get_username(&req)));
+        respond(result)
+    });
 
-        if requests::move_object(&cloned_engine, buf, get_username(&req)) {
-            Ok(Response::with((status::Ok)))
-        } else {
-            Ok(Response::with((status::Ok))) // TODO: Заменить на ошибку
-        }
+    let cloned_engine = mutex.clone();
+    let spec = RouteSpec::new("move")
+        .accepts::<MoveObjectRequest>()
+        .returns::<CommandResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::move_object(&cloned_engine, buf, get_username(&req)));
+        respond(result)
     });
 
     let cloned_engine = mutex.clone();
-    router.add_route("info".to_string(), move |_: &mut Request| {
-        match requests::info(&cloned_engine) {
-            Some(response) => Ok(Response::with((status::Ok, response))),
-            None => Ok(Response::with((status::Ok))), // TODO: Заменить на ошибку, хотя вряд ли она тут будет
-        }
+    let spec = RouteSpec::new("info").returns::<ServerInfo>();
+    router.add_route(spec, move |req: &mut Request| {
+        respond(requests::info(&cloned_engine, get_api_version(&req)))
     });
 
     let cloned_engine = mutex.clone();
-    router.add_route("radar".to_string(),
-                     move |req: &mut Request| match requests::radar(&cloned_engine,
-                                                                    get_username(&req)) {
-                         Some(response) => Ok(Response::with((status::Ok, response))),
-                         None => Ok(Response::with((status::Ok))),
-                     });
+    let spec = RouteSpec::new("player").returns::<PlayerResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        respond(requests::player(&cloned_engine, get_username(&req)))
+    });
 
     let cloned_engine = mutex.clone();
-    router.add_route("weapon_fire".to_owned(), move |req: &mut Request| {
-        let mut buf = String::new();
-        req.body.read_to_string(&mut buf).unwrap();
+    let spec = RouteSpec::new("radar").returns::<Vec<ContactResponse>>();
+    router.add_route(spec, move |req: &mut Request| {
+        respond(requests::radar(&cloned_engine, get_username(&req)))
+    });
 
-        if requests::weapon_fire(&cloned_engine, buf, get_username(&req)) {
-            Ok(Response::with((status::Ok)))
-        } else {
-            Ok(Response::with((status::Ok)))
-        }
+    let cloned_engine = mutex.clone();
+    let spec = RouteSpec::new("weapon_fire")
+        .accepts::<WeaponFireRequest>()
+        .returns::<CommandResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::weapon_fire(&cloned_engine, buf, get_username(&req)));
+        respond(result)
+    });
+
+    let cloned_engine = mutex.clone();
+    let spec = RouteSpec::new("weapon_stop")
+        .accepts::<ObjectIdRequest>()
+        .returns::<CommandResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::weapon_stop(&cloned_engine, buf, get_username(&req)));
+        respond(result)
+    });
+
+    let cloned_engine = mutex.clone();
+    let spec = RouteSpec::new("build")
+        .accepts::<BuildRequest>()
+        .returns::<CommandResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::build(&cloned_engine, buf, get_username(&req)));
+        respond(result)
+    });
+
+    let cloned_engine = mutex.clone();
+    let spec = RouteSpec::new("order")
+        .accepts::<OrderRequest>()
+        .returns::<CommandResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::order(&cloned_engine, buf, get_username(&req)));
+        respond(result)
     });
 
     let cloned_engine = mutex.clone();
-    router.add_route("build".to_owned(), move |req: &mut Request| {
-        let mut buf = String::new();
-        req.body.read_to_string(&mut buf).unwrap();
+    let spec = RouteSpec::new("cancel")
+        .accepts::<ObjectIdRequest>()
+        .returns::<CommandResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::cancel(&cloned_engine, buf, get_username(&req)));
+        respond(result)
+    });
 
-        if requests::build(&cloned_engine, buf, get_username(&req)) {
-            Ok(Response::with((status::Ok)))
-        } else {
-            Ok(Response::with((status::Ok)))
+    let cloned_engine = mutex.clone();
+    let spec = RouteSpec::new("batch")
+        .accepts_schema(requests::batch_schema())
+        .returns::<BatchResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::batch(&cloned_engine, buf, get_username(&req)));
+        match result {
+            Ok((true, body)) => Ok(Response::with((status::Ok, body))),
+            Ok((false, body)) => {
+                // Отказ оформляем как остальные ошибки - с типом JSON
+                let mut response = Response::with((status::BadRequest, body));
+                response.headers.set(ContentType::json());
+                Ok(response)
+            }
+            Err(e) => respond(Err(e)),
         }
     });
 
-    Iron::new(router).http("localhost:3000").unwrap();
+    let cloned_engine = mutex.clone();
+    let spec = RouteSpec::new("admin/limits")
+        .admin()
+        .returns::<Vec<PlayerLimitsResponse>>();
+    router.add_route(spec,
+                     move |_: &mut Request| respond(requests::limits(&cloned_engine)));
+
+    let cloned_engine = mutex.clone();
+    let spec = RouteSpec::new("command_status")
+        .accepts::<CommandStatusRequest>()
+        .returns::<CommandStatusResponse>();
+    router.add_route(spec, move |req: &mut Request| {
+        let result = read_body(req)
+            .and_then(|buf| requests::command_status(&cloned_engine, buf, get_username(&req)));
+        respond(result)
+    });
+
+    let api = RouteSpec::new("api").returns::<ApiResponse>();
+    let mut routes = router.describe();
+    routes.extend(api.versions.iter().map(|&version| api.describe(version)));
+    let description = json::encode(&ApiResponse {
+                                        versions: API_VERSIONS.to_vec(),
+                                        legacy_version: LEGACY_API_VERSION,
+                                        routes: routes,
+                                        error: schema::<ErrorResponse>(),
+                                    })
+            .unwrap();
+    router.add_route(api, move |_: &mut Request| respond(Ok(description.clone())));
+
+    let mut iron = Iron::new(router);
+    iron.threads = threads;
+    match iron.http((address.as_str(), port)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Can't start server on {}:{}: {}", address, port, e)),
+    }
 }
 
-// Возвращает логин пользователя или "unknown" при его отсутствии
+fn respond(result: Result<String, ServerError>) -> IronResult<Response> {
+    match result {
+        Ok(body) => Ok(Response::with((status::Ok, body))),
+        Err(e) => {
+            println!("Ошибка запроса: {}", e.message());
+            Ok(e.to_response())
+        }
+    }
+}
+
+fn read_body(req: &mut Request) -> Result<String, ServerError> {
+    let limit = req.extensions.get::<BodyLimit>().cloned().unwrap();
+    let mut buf = String::new();
+    match req.body.by_ref().take(limit as u64 + 1).read_to_string(&mut buf) {
+        Ok(size) if size > limit => Err(ServerError::PayloadTooLarge(limit)),
+        Ok(_) => Ok(buf),
+        Err(e) => Err(ServerError::MalformedJson(e.to_string())),
+    }
+}
+
+// Возвращает логин пользователя, проверенного в Router
 fn get_username(req: &Request) -> String {
-    match req.headers.get::<Authorization<Basic>>() {
-        Some(expr) => expr.username.clone(),
-        None => "unknown".to_owned(),
+    req.extensions.get::<Username>().cloned().unwrap()
+}
+
+fn get_api_version(req: &Request) -> u32 {
+    req.extensions.get::<ApiVersion>().cloned().unwrap()
+}
+
+// Проверяет логин и пароль из Basic-auth по списку учётных записей.
+// PBKDF2 считается только для пароля, который ещё не подходил
+fn authenticate(req: &Request,
+                accounts: &[PlayerAccount],
+                verified: &Mutex<HashMap<String, String>>)
+                -> Option<String> {
+    let auth = match req.headers.get::<Authorization<Basic>>() {
+        Some(expr) => expr,
+        None => return None,
+    };
+    let password = match auth.password {
+        Some(ref password) => password,
+        None => return None,
+    };
+    let account = match accounts.iter().find(|account| account.username == auth.username) {
+        Some(account) => account,
+        None => return None,
+    };
+    let fingerprint = account.fingerprint(password);
+    let known = verified
+        .lock()
+        .unwrap()
+        .get(&account.username)
+        .map_or(false, |known| constant_time_eq(known, &fingerprint));
+    if known {
+        return Some(account.username.clone());
+    }
+    if !account.check_password(password) {
+        return None;
     }
+    verified.lock().unwrap().insert(account.username.clone(), fingerprint);
+    Some(account.username.clone())
 }
 
-fn check_username(req: &Request, username: String) -> bool {
-    match req.headers.get::<Authorization<Basic>>() {
-        Some(expr) => {
-            if expr.username != username {
-                return false;
-            } else {
-                return true;
-            }
-        }
-        None => false,  
+#[cfg(test)]
+mod tests {
+    use iron::prelude::*;
+    use iron::status;
+    use server::version::LEGACY_API_VERSION;
+    use super::{Router, RouteSpec};
+
+    fn router() -> Router {
+        let mut router = Router::new(vec![], 1024);
+        router.add_route(RouteSpec::new("objects").versions(&[1]),
+                         |_: &mut Request| Ok(Response::with((status::Ok, "v1"))));
+        router.add_route(RouteSpec::new("objects").versions(&[2]),
+                         |_: &mut Request| Ok(Response::with((status::Ok, "v2"))));
+        router.add_route(RouteSpec::new("info").versions(&[1, 2]),
+                         |_: &mut Request| Ok(Response::with((status::Ok, "info"))));
+        router
+    }
+
+    #[test]
+    fn each_version_gets_its_own_route() {
+        let router = router();
+        let (version, ref route) = router.routes["v1/objects"];
+        assert_eq!((version, route.spec.versions.clone()), (1, vec![1]));
+        let (version, ref route) = router.routes["v2/objects"];
+        assert_eq!((version, route.spec.versions.clone()), (2, vec![2]));
+        assert!(!router.routes.contains_key("v3/objects"));
+    }
+
+    #[test]
+    fn shared_route_is_served_under_every_version() {
+        let router = router();
+        assert_eq!(router.routes["v1/info"].0, 1);
+        assert_eq!(router.routes["v2/info"].0, 2);
+    }
+
+    #[test]
+    fn paths_without_prefix_use_legacy_version() {
+        let router = router();
+        assert_eq!(router.routes["objects"].0, LEGACY_API_VERSION);
+        assert_eq!(router.routes["objects"].1.spec.versions, vec![LEGACY_API_VERSION]);
+    }
+
+    #[test]
+    fn describe_lists_versioned_paths_once() {
+        let paths: Vec<String> = router().describe().into_iter().map(|route| route.path).collect();
+        assert_eq!(paths,
+                   vec!["/v1/info", "/v1/objects", "/v2/info", "/v2/objects"]);
     }
 }
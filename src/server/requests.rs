@@ -1,136 +1,589 @@
 use std::sync::{Arc, Mutex};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
 use server::GameEngine;
 use server::events::*;
-use rustc_serialize::json;
+use server::errors::ServerError;
+use server::schema::schema;
+use rustc_serialize::{json, Decodable};
+use rustc_serialize::json::Json;
 use data_types::*;
 
-pub fn world_size(mutex: &Arc<Mutex<GameEngine>>) -> Option<String> {
+// Сколько держать long-poll запрос без новых обновлений
+const LONG_POLL_TIMEOUT: u64 = 25;
+
+pub fn world_size(mutex: &Arc<Mutex<GameEngine>>) -> Result<String, ServerError> {
     let engine = mutex.lock().unwrap();
     let response = WorldSizeResponse {
         width: engine.world_size_x,
         height: engine.world_size_y,
     };
-    Some(json::encode(&response).unwrap())
+    Ok(json::encode(&response).unwrap())
+}
+
+// api_version в ответе - версия, по которой пришёл запрос
+pub fn info(mutex: &Arc<Mutex<GameEngine>>, version: u32) -> Result<String, ServerError> {
+    let mut info = mutex.lock().unwrap().get_server_info();
+    info.api_version = version;
+    Ok(json::encode(&info).unwrap())
 }
 
-pub fn info(mutex: &Arc<Mutex<GameEngine>>) -> Option<String> {
+pub fn objects(mutex: &Arc<Mutex<GameEngine>>, owner: String) -> Result<String, ServerError> {
     let engine = mutex.lock().unwrap();
-    Some(json::encode(&engine.info).unwrap())
+    Ok(json::encode(&engine.objects_for(&owner)).unwrap())
 }
 
-pub fn objects(mutex: &Arc<Mutex<GameEngine>>) -> Option<String> {
+pub fn player(mutex: &Arc<Mutex<GameEngine>>, owner: String) -> Result<String, ServerError> {
     let engine = mutex.lock().unwrap();
-    let objects: Vec<ObjectResponse> = engine
-        .objects
-        .iter()
-        .map(|x| {
-            let (_, obj) = x;
-            ObjectResponse {
-                name: obj.name.clone(),
-                owner: obj.owner.clone(),
-                x: obj.x,
-                y: obj.y,
-                otype: obj.otype.clone(),
-            }
-        })
-        .collect();
-    Some(json::encode(&objects).unwrap())
+    let response = PlayerResponse {
+        resources: engine.get_resources(&owner),
+        name: owner,
+    };
+    Ok(json::encode(&response).unwrap())
 }
 
 pub fn object_info(mutex: &Arc<Mutex<GameEngine>>,
                    raw_json: String,
                    owner: String)
-                   -> Option<String> {
+                   -> Result<String, ServerError> {
     println!("{:?}", &raw_json);
-    let name: NameResponse = json::decode(&raw_json).unwrap();
+    let request: ObjectIdRequest = decode(&raw_json)?;
 
+    let engine = mutex.lock().unwrap();
+    let object = engine.get_owned_object(request.id, &owner)?;
+    Ok(json::encode(&engine.components.info(object)).unwrap())
+}
+
+// Имя только подпись, поэтому меняется сразу, а не через очередь событий
+pub fn rename(mutex: &Arc<Mutex<GameEngine>>,
+              request: String,
+              owner: String)
+              -> Result<String, ServerError> {
+    let req: RenameRequest = decode(&request)?;
     let mut engine = mutex.lock().unwrap();
-    if let Some(object) = engine.get_object(&name.name, Some(&owner)) {
-        Some(json::encode(object).unwrap())
-    } else {
-        None
+    engine.get_owned_object(req.id, &owner)?;
+    match engine.get_object_mut(req.id, None) {
+        Some(object) => {
+            object.name = req.name;
+            Ok(json::encode(&object.to_response()).unwrap())
+        }
+        None => Err(ServerError::ObjectNotFound(req.id)),
     }
 }
 
-pub fn move_object(mutex: &Arc<Mutex<GameEngine>>, input: String, owner: String) -> bool {
-    let mut engine = mutex.lock().unwrap();
-    match json::decode(&input) {
-        Err(e) => {
-            println!("Json parsing error: {:?}", e);
-            return false;
+// Значения поля type у элементов /batch
+const MOVE_COMMAND: &'static str = "move";
+const FIRE_COMMAND: &'static str = "weapon_fire";
+const BUILD_COMMAND: &'static str = "build";
+const ORDER_COMMAND: &'static str = "order";
+const CANCEL_COMMAND: &'static str = "cancel";
+const WEAPON_STOP_COMMAND: &'static str = "weapon_stop";
+
+// Приказ игрока, общий для отдельных маршрутов и /batch
+enum CommandRequest {
+    Order {
+        id: ObjectId,
+        order: Order,
+        append: bool,
+    },
+    Cancel { id: ObjectId },
+    WeaponStop { id: ObjectId },
+}
+
+impl CommandRequest {
+    // Элемент /batch: {"type": "move" | "weapon_fire" | "weapon_stop" | "build" | "order" |
+    // "cancel", ...}
+    fn from_json(item: &Json) -> Result<Self, ServerError> {
+        let kind = match item.find("type").and_then(|kind| kind.as_string()) {
+            Some(kind) => kind.to_owned(),
+            None => return Err(ServerError::MalformedJson("Missing command type".to_owned())),
+        };
+        match kind.as_str() {
+            MOVE_COMMAND => Ok(CommandRequest::from_move(decode_json(item)?)),
+            FIRE_COMMAND => Ok(CommandRequest::from_fire(decode_json(item)?)),
+            BUILD_COMMAND => Ok(CommandRequest::from_build(decode_json(item)?)),
+            ORDER_COMMAND => CommandRequest::from_order(decode_json(item)?),
+            CANCEL_COMMAND => Ok(CommandRequest::from_cancel(decode_json(item)?)),
+            WEAPON_STOP_COMMAND => Ok(CommandRequest::from_weapon_stop(decode_json(item)?)),
+            _ => Err(ServerError::MalformedJson(format!("Unknown command type {}", kind))),
+        }
+    }
+
+    fn new_order(id: ObjectId, kind: OrderType, x: f64, y: f64, append: Option<bool>) -> Self {
+        CommandRequest::Order {
+            id: id,
+            order: Order {
+                command: 0,
+                kind: kind,
+                x: x,
+                y: y,
+                from_x: x,
+                from_y: y,
+                b_type: None,
+                b_name: None,
+                b_cost: 0.0,
+                b_time: 0.0,
+                progress: 0.0,
+            },
+            append: append.unwrap_or(false),
+        }
+    }
+
+    fn from_move(req: MoveObjectRequest) -> Self {
+        CommandRequest::new_order(req.id, OrderType::Move, req.x, req.y, req.append)
+    }
+
+    fn from_fire(req: WeaponFireRequest) -> Self {
+        CommandRequest::new_order(req.id, OrderType::Attack, req.x, req.y, req.append)
+    }
+
+    fn from_build(req: BuildRequest) -> Self {
+        let mut request =
+            CommandRequest::new_order(req.id, OrderType::Build, 0.0, 0.0, req.append);
+        // Цена и время постройки берутся из описания типа при постановке в очередь
+        if let CommandRequest::Order { ref mut order, .. } = request {
+            order.b_type = Some(req.otype);
+            order.b_name = Some(req.oname);
         }
-        Ok(data) => {
-            let mvr: MoveObjectRequest = data;
-
-            println!("Передвижение объекта {} -- x: {} y: {}",
-                     mvr.name,
-                     mvr.x,
-                     mvr.y);
-
-            engine.add_event(Event::MoveRequest(NetworkMoveEvent {
-                                                    name: mvr.name,
-                                                    owner: owner,
-                                                    dest_x: mvr.x,
-                                                    dest_y: mvr.y,
-                                                }));
+        request
+    }
+
+    fn from_order(req: OrderRequest) -> Result<Self, ServerError> {
+        match req.kind {
+            OrderType::Build => {
+                match (req.otype, req.oname) {
+                    (Some(otype), Some(oname)) => {
+                        Ok(CommandRequest::from_build(BuildRequest {
+                                                          id: req.id,
+                                                          oname: oname,
+                                                          otype: otype,
+                                                          append: req.append,
+                                                      }))
+                    }
+                    _ => Err(ServerError::MalformedJson("Build order needs otype and oname"
+                                                            .to_owned())),
+                }
+            }
+            OrderType::Hold | OrderType::Unload => {
+                Ok(CommandRequest::new_order(req.id, req.kind, 0.0, 0.0, req.append))
+            }
+            _ => {
+                match (req.x, req.y) {
+                    (Some(x), Some(y)) => {
+                        Ok(CommandRequest::new_order(req.id, req.kind, x, y, req.append))
+                    }
+                    _ => Err(ServerError::MalformedJson(format!("{:?} order needs x and y",
+                                                                req.kind))),
+                }
+            }
+        }
+    }
+
+    fn from_cancel(req: ObjectIdRequest) -> Self {
+        CommandRequest::Cancel { id: req.id }
+    }
+
+    fn from_weapon_stop(req: ObjectIdRequest) -> Self {
+        CommandRequest::WeaponStop { id: req.id }
+    }
+
+    // Сколько мест в очереди событий занимает приказ, см. RateLimiter
+    fn queued(&self) -> usize {
+        match *self {
+            CommandRequest::Order { .. } => 1,
+            CommandRequest::Cancel { .. } |
+            CommandRequest::WeaponStop { .. } => 0,
+        }
+    }
+
+    // reserved - ресурсы, уже обещанные постройкам из того же /batch
+    fn validate(&self,
+                engine: &GameEngine,
+                owner: &String,
+                reserved: f64)
+                -> Result<(), ServerError> {
+        let (id, order) = match *self {
+            CommandRequest::Order { id, ref order, .. } => (id, order),
+            CommandRequest::Cancel { id } |
+            CommandRequest::WeaponStop { id } => {
+                return engine.get_owned_object(id, owner).map(|_| ())
+            }
+        };
+        let object = engine.get_owned_object(id, owner)?;
+        match order.kind {
+            OrderType::Move | OrderType::Patrol => {
+                if !engine.components.can_move(id) {
+                    return Err(ServerError::NoDrive(id));
+                }
+            }
+            OrderType::Attack => check_weapons(engine, object, order, false)?,
+            OrderType::Harvest => {
+                check_weapons(engine, object, order, true)?;
+                if !engine.components.has_mining_cargo(id) {
+                    return Err(ServerError::NoMiningCargo(id));
+                }
+            }
+            OrderType::Unload => {
+                if !engine.components.has_mining_cargo(id) {
+                    return Err(ServerError::NoMiningCargo(id));
+                }
+            }
+            OrderType::Build => {
+                if let Some(ref b_type) = order.b_type {
+                    let definition = match engine.units.get(b_type) {
+                        Some(definition) => definition,
+                        None => return Err(ServerError::UnknownObjectType(b_type.clone())),
+                    };
+                    let builder = engine.units.get(&object.otype);
+                    if !builder.map_or(false, |unit| unit.can_build(b_type)) {
+                        return Err(ServerError::CantBuild(id, b_type.clone()));
+                    }
+                    if engine.get_resources(owner) < reserved + definition.build_cost {
+                        return Err(ServerError::InsufficientResources(owner.clone()));
+                    }
+                }
+            }
+            OrderType::Hold => {}
+        }
+        Ok(())
+    }
+
+    // Сколько стоит постройка, 0 для остальных приказов
+    fn build_cost(&self, engine: &GameEngine) -> f64 {
+        match *self {
+            CommandRequest::Order { ref order, .. } if order.kind == OrderType::Build => {
+                order
+                    .b_type
+                    .as_ref()
+                    .and_then(|b_type| engine.units.get(b_type))
+                    .map_or(0.0, |unit| unit.build_cost)
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn into_event(self, command: CommandId, owner: String) -> Event {
+        match self {
+            CommandRequest::Order { id, mut order, append } => {
+                match order.kind {
+                    OrderType::Build => {
+                        println!("Постройка объекта {} при помощи {}",
+                                 order.b_name.clone().unwrap_or_default(),
+                                 id)
+                    }
+                    OrderType::Hold => println!("Объект {} держит позицию", id),
+                    OrderType::Unload => println!("Объект {} сдаёт руду", id),
+                    _ => {
+                        println!("Приказ {:?} объекту {} -- x: {} y: {}",
+                                 order.kind,
+                                 id,
+                                 order.x,
+                                 order.y)
+                    }
+                }
+                order.command = command;
+                Event::OrderRequest(NetworkOrderEvent {
+                                        command: command,
+                                        id: id,
+                                        owner: owner,
+                                        order: order,
+                                        append: append,
+                                    })
+            }
+            CommandRequest::Cancel { id } => {
+                println!("Отмена приказов объекта {}", id);
+                Event::CancelRequest(NetworkCancelEvent {
+                                         command: command,
+                                         id: id,
+                                         owner: owner,
+                                     })
+            }
+            CommandRequest::WeaponStop { id } => {
+                println!("Объект {} прекращает огонь", id);
+                Event::WeaponStopRequest(NetworkCancelEvent {
+                                             command: command,
+                                             id: id,
+                                             owner: owner,
+                                         })
+            }
         }
     }
-    true
 }
 
-pub fn radar(mutex: &Arc<Mutex<GameEngine>>, owner: String) -> Option<String> {
+// Для Attack подходит любое оружие, для Harvest только добывающее.
+// Объект без двигателя должен достать цель с места
+fn check_weapons(engine: &GameEngine,
+                 object: &SampleObject,
+                 order: &Order,
+                 mining: bool)
+                 -> Result<(), ServerError> {
+    let weapons: Vec<&Weapon> = engine.components
+        .weapons
+        .get(object.id)
+        .iter()
+        .filter(|weapon| !mining || weapon.wtype.is_mining())
+        .collect();
+    if weapons.is_empty() {
+        return Err(ServerError::NoWeapon(object.id));
+    }
+    if !weapons.iter().any(|weapon| engine.components.energy(object.id) >= weapon.energy) {
+        return Err(ServerError::InsufficientEnergy(object.id));
+    }
+    if !engine.components.can_move(object.id) &&
+       !weapons.iter().all(|weapon| weapon.in_range(object, order.x, order.y)) {
+        return Err(ServerError::OutOfRange(object.id));
+    }
+    Ok(())
+}
+
+fn submit(mutex: &Arc<Mutex<GameEngine>>,
+          request: CommandRequest,
+          owner: String)
+          -> Result<String, ServerError> {
+    let mut engine = mutex.lock().unwrap();
+    request.validate(&engine, &owner, 0.0)?;
+    engine.limits.acquire(&owner, 1, request.queued())?;
+
+    let command = engine.new_command(&owner);
+    engine.add_event(request.into_event(command, owner));
+    Ok(json::encode(&CommandResponse { command: command }).unwrap())
+}
+
+pub fn move_object(mutex: &Arc<Mutex<GameEngine>>,
+                   input: String,
+                   owner: String)
+                   -> Result<String, ServerError> {
+    submit(mutex, CommandRequest::from_move(decode(&input)?), owner)
+}
+
+pub fn radar(mutex: &Arc<Mutex<GameEngine>>, owner: String) -> Result<String, ServerError> {
     let engine = mutex.lock().unwrap();
     let scan_result = engine.radar_scan(&owner, true);
-    Some(json::encode(&scan_result).unwrap())
+    Ok(json::encode(&scan_result).unwrap())
+}
+
+pub fn weapon_fire(mutex: &Arc<Mutex<GameEngine>>,
+                   request: String,
+                   owner: String)
+                   -> Result<String, ServerError> {
+    submit(mutex, CommandRequest::from_fire(decode(&request)?), owner)
+}
+
+pub fn weapon_stop(mutex: &Arc<Mutex<GameEngine>>,
+                   request: String,
+                   owner: String)
+                   -> Result<String, ServerError> {
+    submit(mutex, CommandRequest::from_weapon_stop(decode(&request)?), owner)
 }
 
-pub fn weapon_fire(mutex: &Arc<Mutex<GameEngine>>, request: String, owner: String) -> bool {
+pub fn build(mutex: &Arc<Mutex<GameEngine>>,
+             request: String,
+             owner: String)
+             -> Result<String, ServerError> {
+    submit(mutex, CommandRequest::from_build(decode(&request)?), owner)
+}
+
+pub fn order(mutex: &Arc<Mutex<GameEngine>>,
+             request: String,
+             owner: String)
+             -> Result<String, ServerError> {
+    submit(mutex, CommandRequest::from_order(decode(&request)?)?, owner)
+}
+
+pub fn cancel(mutex: &Arc<Mutex<GameEngine>>,
+              request: String,
+              owner: String)
+              -> Result<String, ServerError> {
+    submit(mutex, CommandRequest::from_cancel(decode(&request)?), owner)
+}
+
+// Проверяет все приказы и ставит их в очередь одним событием,
+// если хотя бы один не прошёл проверку - не ставит ни одного.
+// Возвращает флаг принятия и ответ с результатом по каждому приказу
+pub fn batch(mutex: &Arc<Mutex<GameEngine>>,
+             request: String,
+             owner: String)
+             -> Result<(bool, String), ServerError> {
+    let items = match Json::from_str(&request) {
+        Ok(Json::Array(items)) => items,
+        Ok(_) => return Err(ServerError::MalformedJson("Expected array of commands".to_owned())),
+        Err(e) => return Err(ServerError::MalformedJson(e.to_string())),
+    };
+    let requests: Vec<Result<CommandRequest, ServerError>> =
+        items.iter().map(CommandRequest::from_json).collect();
+
     let mut engine = mutex.lock().unwrap();
-    match json::decode(&request) {
-        Err(e) => {
-            println!("Json parsing error: {:?}", e);
-            return false;
+    // Постройки пакета оплачиваются из одного банка, поэтому их цена складывается
+    let mut reserved = 0.0;
+    let mut errors: Vec<Option<ServerError>> = vec![];
+    for request in requests.iter() {
+        errors.push(match *request {
+                        Ok(ref request) => {
+                            let error = request.validate(&engine, &owner, reserved).err();
+                            reserved += request.build_cost(&engine);
+                            error
+                        }
+                        Err(ref e) => Some(e.clone()),
+                    });
+    }
+
+    let accepted = errors.iter().all(|error| error.is_none());
+    let results = if accepted {
+        // Лимит списывается только за принятые приказы
+        let queued = requests
+            .iter()
+            .filter_map(|request| request.as_ref().ok())
+            .map(|request| request.queued())
+            .sum();
+        engine.limits.acquire(&owner, requests.len(), queued)?;
+        let mut events = vec![];
+        let mut results = vec![];
+        for request in requests.into_iter().filter_map(|request| request.ok()) {
+            let command = engine.new_command(&owner);
+            events.push(request.into_event(command, owner.clone()));
+            results.push(BatchResult {
+                             command: Some(command),
+                             error: None,
+                         });
         }
-        Ok(data) => {
-            let wfr: WeaponFireRequest = data;
-            println!("Огонь объекта {} -- x: {} y: {}",
-                     wfr.name,
-                     wfr.x,
-                     wfr.y);
-
-            engine.add_event(Event::FireRequest(NetworkFireEvent {
-                                                    name: wfr.name,
-                                                    owner: owner,
-                                                    dest_x: wfr.x,
-                                                    dest_y: wfr.y,
-                                                }));
+        engine.add_event(Event::Batch(events));
+        results
+    } else {
+        errors
+            .iter()
+            .map(|error| {
+                     BatchResult {
+                         command: None,
+                         error: error.as_ref().map(|e| e.to_error_response()),
+                     }
+                 })
+            .collect()
+    };
+    Ok((accepted,
+        json::encode(&BatchResponse {
+                         accepted: accepted,
+                         results: results,
+                     })
+                .unwrap()))
+}
+
+pub fn command_status(mutex: &Arc<Mutex<GameEngine>>,
+                      request: String,
+                      owner: String)
+                      -> Result<String, ServerError> {
+    let req: CommandStatusRequest = decode(&request)?;
+    let engine = mutex.lock().unwrap();
+    let command = engine.get_command(req.command, &owner)?;
+
+    let (status, error) = match command.state {
+        CommandState::Queued => ("Queued", None),
+        CommandState::Rejected(ref e) => ("Rejected", Some(e.to_error_response())),
+        CommandState::InProgress => ("InProgress", None),
+        CommandState::Completed => ("Completed", None),
+        CommandState::Cancelled => ("Cancelled", None),
+    };
+    Ok(json::encode(&CommandStatusResponse {
+                        command: req.command,
+                        status: status.to_owned(),
+                        object: command.object,
+                        error: error,
+                    })
+               .unwrap())
+}
+
+// Схема тела /batch: массив приказов, тип которых задаётся полем type
+pub fn batch_schema() -> Json {
+    let mut variants = BTreeMap::new();
+    variants.insert(MOVE_COMMAND.to_owned(), tagged_schema::<MoveObjectRequest>(MOVE_COMMAND));
+    variants.insert(FIRE_COMMAND.to_owned(), tagged_schema::<WeaponFireRequest>(FIRE_COMMAND));
+    variants.insert(BUILD_COMMAND.to_owned(), tagged_schema::<BuildRequest>(BUILD_COMMAND));
+    variants.insert(ORDER_COMMAND.to_owned(), tagged_schema::<OrderRequest>(ORDER_COMMAND));
+    variants.insert(CANCEL_COMMAND.to_owned(), tagged_schema::<ObjectIdRequest>(CANCEL_COMMAND));
+    variants.insert(WEAPON_STOP_COMMAND.to_owned(),
+                    tagged_schema::<ObjectIdRequest>(WEAPON_STOP_COMMAND));
+
+    let mut one_of = BTreeMap::new();
+    one_of.insert("one_of".to_owned(), Json::Object(variants));
+    let mut array = BTreeMap::new();
+    array.insert("array".to_owned(), Json::Object(one_of));
+    Json::Object(array)
+}
+
+fn tagged_schema<T: Decodable>(kind: &str) -> Json {
+    match schema::<T>() {
+        Json::Object(mut fields) => {
+            fields.insert("type".to_owned(), Json::String(kind.to_owned()));
+            Json::Object(fields)
         }
+        other => other,
     }
-    true
 }
 
-pub fn build(mutex: &Arc<Mutex<GameEngine>>, request: String, owner: String) -> bool {
+pub fn limits(mutex: &Arc<Mutex<GameEngine>>) -> Result<String, ServerError> {
+    let engine = mutex.lock().unwrap();
+    Ok(json::encode(&engine.limits.stats()).unwrap())
+}
+
+// Long-poll: ждёт обновлений мира игрока новее since, без since отдаёт весь его мир.
+// Ждущие запросы занимают не больше половины потоков сервера, остальным
+// сразу отвечаем пустым списком
+pub fn updates(mutex: &Arc<Mutex<GameEngine>>,
+               request: String,
+               owner: String)
+               -> Result<String, ServerError> {
+    let req: UpdatesRequest = if request.is_empty() {
+        UpdatesRequest { since: None }
+    } else {
+        decode(&request)?
+    };
+    let deadline = Instant::now() + Duration::from_secs(LONG_POLL_TIMEOUT);
     let mut engine = mutex.lock().unwrap();
-    match json::decode(&request) {
-        Err(e) => {
-            println!("Json parsing error: {:?}", e);
-            return false;
+    engine.subscribe_updates(&owner);
+    let notifier = engine.updates.notifier();
+    loop {
+        let now = Instant::now();
+        let wait = now < deadline && engine.updates.waiting < engine.config.threads / 2;
+        let response = {
+            let log = &engine.updates;
+            let updates = match req.since {
+                Some(since) => log.since(&owner, since),
+                None => None,
+            };
+            match updates {
+                Some(ref updates) if updates.is_empty() && wait => None,
+                Some(updates) => {
+                    Some(UpdatesResponse {
+                             revision: log.revision(&owner),
+                             reset: false,
+                             updates: updates,
+                         })
+                }
+                None => {
+                    Some(UpdatesResponse {
+                             revision: log.revision(&owner),
+                             reset: true,
+                             updates: vec![log.snapshot(&owner)],
+                         })
+                }
+            }
+        };
+        if let Some(response) = response {
+            return Ok(json::encode(&response).unwrap());
         }
-        Ok(data) => {
-            let req: BuildRequest = data;
-
-            println!("Постройка объекта {} при помощи {}",
-                     req.oname,
-                     req.name);
+        engine.updates.waiting += 1;
+        engine = notifier.wait_timeout(engine, deadline - now).unwrap().0;
+        engine.updates.waiting -= 1;
+    }
+}
 
-            engine.add_event(Event::BuildRequest(NetworkBuildEvent {
-                                                     name: req.name,
-                                                     owner: owner,
-                                                     b_type: req.otype,
-                                                     b_name: req.oname,
-                                                 }));
+fn decode_json<T: Decodable>(item: &Json) -> Result<T, ServerError> {
+    let mut decoder = json::Decoder::new(item.clone());
+    Decodable::decode(&mut decoder).map_err(|e| ServerError::MalformedJson(e.to_string()))
+}
 
-        }
-    }
-    true
+fn decode<T: Decodable>(raw_json: &str) -> Result<T, ServerError> {
+    json::decode(raw_json).map_err(|e| {
+                                       println!("Json parsing error: {:?}", e);
+                                       ServerError::MalformedJson(e.to_string())
+                                   })
 }
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use rustc_serialize::{Decodable, Decoder};
+use rustc_serialize::json::Json;
+
+// Описание JSON формата типа, получаемое из его реализации Decodable.
+// Примитивы описываются строкой ("f64", "string"...), структуры - объектом с полями,
+// перечисления - {"enum": [...]}, Option - {"optional": ...}, Vec - {"array": ...}
+pub fn schema<T: Decodable>() -> Json {
+    let mut decoder = SchemaDecoder {
+        output: vec![],
+        structs: vec![],
+    };
+    match T::decode(&mut decoder) {
+        Ok(_) => decoder.output.pop().unwrap_or(Json::Null),
+        Err(e) => Json::String(format!("Schema error: {}", e)),
+    }
+}
+
+// Декодер, который не читает данные, а записывает, что у него просили прочитать
+struct SchemaDecoder {
+    output: Vec<Json>,
+    structs: Vec<BTreeMap<String, Json>>,
+}
+
+impl SchemaDecoder {
+    fn emit(&mut self, schema: Json) {
+        self.output.push(schema);
+    }
+
+    fn primitive(&mut self, name: &str) {
+        self.emit(Json::String(name.to_owned()));
+    }
+
+    fn wrap(&mut self, kind: &str) {
+        let inner = self.output.pop().unwrap_or(Json::Null);
+        let mut object = BTreeMap::new();
+        object.insert(kind.to_owned(), inner);
+        self.emit(Json::Object(object));
+    }
+
+    fn begin_struct(&mut self) {
+        self.structs.push(BTreeMap::new());
+    }
+
+    fn end_struct(&mut self) {
+        let fields = self.structs.pop().unwrap_or_default();
+        self.emit(Json::Object(fields));
+    }
+
+    fn field(&mut self, name: &str) {
+        let schema = self.output.pop().unwrap_or(Json::Null);
+        if let Some(fields) = self.structs.last_mut() {
+            fields.insert(name.to_owned(), schema);
+        }
+    }
+
+    fn tuple(&mut self, start: usize) {
+        let items = self.output.split_off(start);
+        let mut object = BTreeMap::new();
+        object.insert("tuple".to_owned(), Json::Array(items));
+        self.emit(Json::Object(object));
+    }
+
+    fn variants(&mut self, names: &[&str]) {
+        let names = names
+            .iter()
+            .map(|name| Json::String(name.to_string()))
+            .collect();
+        let mut object = BTreeMap::new();
+        object.insert("enum".to_owned(), Json::Array(names));
+        self.emit(Json::Object(object));
+    }
+}
+
+impl Decoder for SchemaDecoder {
+    type Error = String;
+
+    fn read_nil(&mut self) -> Result<(), String> {
+        self.primitive("null");
+        Ok(())
+    }
+    fn read_usize(&mut self) -> Result<usize, String> {
+        self.primitive("usize");
+        Ok(0)
+    }
+    fn read_u64(&mut self) -> Result<u64, String> {
+        self.primitive("u64");
+        Ok(0)
+    }
+    fn read_u32(&mut self) -> Result<u32, String> {
+        self.primitive("u32");
+        Ok(0)
+    }
+    fn read_u16(&mut self) -> Result<u16, String> {
+        self.primitive("u16");
+        Ok(0)
+    }
+    fn read_u8(&mut self) -> Result<u8, String> {
+        self.primitive("u8");
+        Ok(0)
+    }
+    fn read_isize(&mut self) -> Result<isize, String> {
+        self.primitive("isize");
+        Ok(0)
+    }
+    fn read_i64(&mut self) -> Result<i64, String> {
+        self.primitive("i64");
+        Ok(0)
+    }
+    fn read_i32(&mut self) -> Result<i32, String> {
+        self.primitive("i32");
+        Ok(0)
+    }
+    fn read_i16(&mut self) -> Result<i16, String> {
+        self.primitive("i16");
+        Ok(0)
+    }
+    fn read_i8(&mut self) -> Result<i8, String> {
+        self.primitive("i8");
+        Ok(0)
+    }
+    fn read_bool(&mut self) -> Result<bool, String> {
+        self.primitive("bool");
+        Ok(false)
+    }
+    fn read_f64(&mut self) -> Result<f64, String> {
+        self.primitive("f64");
+        Ok(0.0)
+    }
+    fn read_f32(&mut self) -> Result<f32, String> {
+        self.primitive("f32");
+        Ok(0.0)
+    }
+    fn read_char(&mut self) -> Result<char, String> {
+        self.primitive("char");
+        Ok(' ')
+    }
+    fn read_str(&mut self) -> Result<String, String> {
+        self.primitive("string");
+        Ok(String::new())
+    }
+
+    fn read_enum<T, F>(&mut self, _: &str, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        f(self)
+    }
+    fn read_enum_variant<T, F>(&mut self, names: &[&str], mut f: F) -> Result<T, String>
+        where F: FnMut(&mut Self, usize) -> Result<T, String>
+    {
+        let start = self.output.len();
+        let value = f(self, 0)?;
+        self.output.truncate(start);
+        self.variants(names);
+        Ok(value)
+    }
+    fn read_enum_variant_arg<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        f(self)
+    }
+    fn read_enum_struct_variant<T, F>(&mut self, names: &[&str], f: F) -> Result<T, String>
+        where F: FnMut(&mut Self, usize) -> Result<T, String>
+    {
+        self.read_enum_variant(names, f)
+    }
+    fn read_enum_struct_variant_field<T, F>(&mut self, _: &str, _: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        f(self)
+    }
+
+    fn read_struct<T, F>(&mut self, _: &str, _: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        self.begin_struct();
+        let value = f(self)?;
+        self.end_struct();
+        Ok(value)
+    }
+    fn read_struct_field<T, F>(&mut self, name: &str, _: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        let value = f(self)?;
+        self.field(name);
+        Ok(value)
+    }
+
+    fn read_tuple<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        let start = self.output.len();
+        let value = f(self)?;
+        self.tuple(start);
+        Ok(value)
+    }
+    fn read_tuple_arg<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        f(self)
+    }
+    fn read_tuple_struct<T, F>(&mut self, _: &str, len: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        self.read_tuple(len, f)
+    }
+    fn read_tuple_struct_arg<T, F>(&mut self, idx: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        self.read_tuple_arg(idx, f)
+    }
+
+    fn read_option<T, F>(&mut self, mut f: F) -> Result<T, String>
+        where F: FnMut(&mut Self, bool) -> Result<T, String>
+    {
+        let value = f(self, true)?;
+        self.wrap("optional");
+        Ok(value)
+    }
+
+    fn read_seq<T, F>(&mut self, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self, usize) -> Result<T, String>
+    {
+        let value = f(self, 1)?;
+        self.wrap("array");
+        Ok(value)
+    }
+    fn read_seq_elt<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        f(self)
+    }
+
+    fn read_map<T, F>(&mut self, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self, usize) -> Result<T, String>
+    {
+        self.begin_struct();
+        let value = f(self, 1)?;
+        self.end_struct();
+        self.wrap("map");
+        Ok(value)
+    }
+    fn read_map_elt_key<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        let value = f(self)?;
+        self.field("key");
+        Ok(value)
+    }
+    fn read_map_elt_val<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+        where F: FnOnce(&mut Self) -> Result<T, String>
+    {
+        let value = f(self)?;
+        self.field("value");
+        Ok(value)
+    }
+
+    fn error(&mut self, err: &str) -> String {
+        err.to_owned()
+    }
+}
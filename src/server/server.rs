@@ -1,32 +1,102 @@
 use data_types::*;
 use server::events::*;
-use server::config::GameConfig;
+use server::config::{GameConfig, ADMIN};
+use server::damage::DamageTable;
+use server::units::UnitRegistry;
+use server::components::Components;
+use server::errors::ServerError;
+use server::updates::UpdateLog;
+use server::limits::RateLimiter;
+use server::spatial::{SpatialGrid, DEFAULT_CELL_SIZE};
+use server::version::API_VERSIONS;
 use std::collections::{HashMap, VecDeque};
+use std::f64;
 use data_types::{ServerInfo, ObjectResponse};
+use rand::{SeedableRng, XorShiftRng};
+
+// Длительность одного тика симуляции (60 TPS)
+pub const TICK_TIME: f64 = 1.0 / 60.0;
+// Сколько тиков можно догнать за один вызов update, остальное отбрасывается
+const MAX_TICKS_PER_UPDATE: u32 = 10;
+// На каком расстоянии от своего Builder объект считается пристыкованным:
+// можно разгрузить руду и зарядить батарею
+const DOCK_RADIUS: f64 = 20.0;
+// Сколько тиков статус завершённой команды доступен в /command_status (минута при 60 TPS)
+const COMMAND_RETENTION: u64 = 3600;
+
+// Итог выполнения первого приказа из очереди за один тик
+enum OrderResult {
+    Continue,
+    Done,
+    Failed(ServerError),
+}
 
 pub struct GameEngine {
     pub info: ServerInfo,
-    pub objects: HashMap<String, SampleObject>,
+    pub objects: HashMap<ObjectId, SampleObject>,
     pub world_size_x: f64,
     pub world_size_y: f64,
     pub events: VecDeque<Event>,
+    pub commands: HashMap<CommandId, Command>,
+    // Завершённые команды с тиком завершения, по возрастанию тика
+    finished_commands: VecDeque<(CommandId, u64)>,
+    pub updates: UpdateLog,
+    pub limits: RateLimiter,
     pub config: GameConfig,
+    pub damage: DamageTable,
+    pub units: UnitRegistry,
+    // Двигатели, оружие, трюмы и прочее по номерам объектов,
+    // удаляются вместе с объектом
+    pub components: Components,
+    pub tick: u64,
+    // Единственный источник случайности мира: одинаковый seed и одинаковые
+    // приказы дают одинаковый мир
+    pub rng: XorShiftRng,
+    // Банк ресурсов каждого игрока
+    pub resources: HashMap<String, f64>,
+    // Позиции объектов для поиска по радиусу, обновляются вместе с objects
+    spatial: SpatialGrid<ObjectId>,
+    next_object: ObjectId,
+    next_command: CommandId,
+    accumulator: f64,
 }
 
 impl GameEngine {
-    pub fn new(width: f64, height: f64) -> Self {
-        let config = GameConfig::new("config/engine.json");
+    pub fn new(width: f64,
+               height: f64,
+               config: GameConfig,
+               damage: DamageTable,
+               units: UnitRegistry,
+               seed: u64)
+               -> Self {
         GameEngine {
             objects: HashMap::new(),
             info: ServerInfo {
                 name: config.servername.clone(),
                 status: "Ok".to_string(),
                 tps: 0u16,
+                tick: 0,
+                api_version: *API_VERSIONS.last().unwrap(),
+                api_versions: API_VERSIONS.to_vec(),
             },
             world_size_x: width,
             world_size_y: height,
             events: VecDeque::new(),
+            commands: HashMap::new(),
+            finished_commands: VecDeque::new(),
+            updates: UpdateLog::new(),
+            limits: RateLimiter::new(config.commands_per_second, config.max_queued_commands),
             config: config,
+            damage: damage,
+            units: units,
+            components: Components::new(),
+            tick: 0,
+            rng: seeded_rng(seed),
+            resources: HashMap::new(),
+            spatial: SpatialGrid::new(DEFAULT_CELL_SIZE),
+            next_object: 1,
+            next_command: 0,
+            accumulator: 0.0,
         }
     }
 
@@ -38,35 +108,68 @@ impl GameEngine {
         self.info.tps = tps;
     }
 
-    pub fn get_objects(&self) -> HashMap<String, ObjectResponse> {
+    pub fn get_objects(&self) -> HashMap<ObjectId, ObjectResponse> {
         self.objects
             .iter()
-            .map(|(k, v)| {
-                (k.clone(),
-                 ObjectResponse {
-                     name: k.clone(),
-                     owner: v.owner.clone(),
-                     x: v.x,
-                     y: v.y,
-                     otype: v.otype.clone(),
-                 })
-            })
+            .map(|(k, v)| (*k, v.to_response()))
             .collect()
     }
 
+    // Создаёт объект с новым номером, имя остаётся только подписью
     pub fn add_object(&mut self,
                       object_name: String,
                       coord_x: f64,
                       coord_y: f64,
                       otype: ObjectType,
-                      owner: String) {
-        self.objects
-            .insert(object_name.clone(),
-                    SampleObject::new(owner, object_name, otype, coord_x, coord_y));
+                      owner: String)
+                      -> Result<ObjectId, ServerError> {
+        let components = match self.units.get(&otype) {
+            Some(definition) => definition.components.clone(),
+            None => return Err(ServerError::UnknownObjectType(otype)),
+        };
+        let id = self.next_object;
+        self.next_object += 1;
+        self.insert_object(SampleObject::new(id, owner, object_name, otype, coord_x, coord_y));
+        self.components.add(id, &components);
+        // Новый объект стоит в точке появления, а не едет к (0, 0)
+        if let Some(drive) = self.components.drives.first_mut(id) {
+            drive.move_to(coord_x, coord_y);
+        }
+        Ok(id)
+    }
+
+    pub fn object_info(&self, id: ObjectId) -> Option<ObjectInfoResponse> {
+        self.objects.get(&id).map(|object| self.components.info(object))
+    }
+
+    // Все добавления и удаления объектов идут через эти методы,
+    // чтобы пространственный индекс не расходился с objects
+    fn insert_object(&mut self, object: SampleObject) {
+        self.spatial.insert(object.id, object.x, object.y);
+        self.objects.insert(object.id, object);
+    }
+
+    fn remove_object(&mut self, id: ObjectId) {
+        self.spatial.remove(&id);
+        self.objects.remove(&id);
+        self.components.remove(id);
+    }
+
+    pub fn get_resources(&self, player: &String) -> f64 {
+        self.resources.get(player).cloned().unwrap_or(0.0)
+    }
+
+    // Объекты не дальше radius от точки
+    pub fn objects_in_radius(&self, x: f64, y: f64, radius: f64) -> Vec<&SampleObject> {
+        self.spatial
+            .query(x, y, radius)
+            .into_iter()
+            .filter_map(|id| self.objects.get(id))
+            .collect()
     }
 
-    pub fn get_object_with_owner(&self, name: String, owner: String) -> Option<SampleObject> {
-        match self.objects.get(&name) {
+    pub fn get_object_with_owner(&self, id: ObjectId, owner: String) -> Option<SampleObject> {
+        match self.objects.get(&id) {
             Some(obj) => {
                 if obj.owner != owner {
                     return None;
@@ -86,8 +189,8 @@ impl GameEngine {
         }
     }
 
-    pub fn check_object_exsists(&self, name: &String, owner: Option<&String>) -> bool {
-        if let Some(object) = self.objects.get(name) {
+    pub fn check_object_exsists(&self, id: ObjectId, owner: Option<&String>) -> bool {
+        if let Some(object) = self.objects.get(&id) {
             if let Some(owner) = owner {
                 object.owner.eq(owner)
             } else {
@@ -99,10 +202,10 @@ impl GameEngine {
     }
 
     pub fn get_object_mut(&mut self,
-                          name: &String,
+                          id: ObjectId,
                           owner: Option<&String>)
                           -> Option<&mut SampleObject> {
-        if let Some(object) = self.objects.get_mut(name) {
+        if let Some(object) = self.objects.get_mut(&id) {
             if let Some(owner) = owner {
                 if object.owner.eq(owner) {
                     Some(object)
@@ -117,8 +220,8 @@ impl GameEngine {
         }
     }
 
-    pub fn get_object(&mut self, name: &String, owner: Option<&String>) -> Option<&SampleObject> {
-        if let Some(object) = self.objects.get(name) {
+    pub fn get_object(&mut self, id: ObjectId, owner: Option<&String>) -> Option<&SampleObject> {
+        if let Some(object) = self.objects.get(&id) {
             if let Some(owner) = owner {
                 if object.owner.eq(owner) {
                     Some(object)
@@ -133,160 +236,1120 @@ impl GameEngine {
         }
     }
 
-    pub fn game_loop(&mut self, elapsed: f64) {
-        self.event(elapsed);
+    // Объект игрока, с различием между отсутствием объекта и чужим объектом
+    pub fn get_owned_object(&self,
+                            id: ObjectId,
+                            owner: &String)
+                            -> Result<&SampleObject, ServerError> {
+        match self.objects.get(&id) {
+            Some(object) => {
+                if object.check_owner(Some(owner)) {
+                    Ok(object)
+                } else {
+                    Err(ServerError::NotOwner(id))
+                }
+            }
+            None => Err(ServerError::ObjectNotFound(id)),
+        }
+    }
+
+    // Накапливает реальное время и выполняет столько тиков фиксированной
+    // длины, сколько в него помещается. Возвращает число выполненных тиков
+    pub fn update(&mut self, elapsed: f64) -> u16 {
+        self.accumulator += elapsed;
+        let mut ticks = 0;
+        while self.accumulator >= TICK_TIME {
+            if ticks == MAX_TICKS_PER_UPDATE {
+                self.accumulator %= TICK_TIME;
+                break;
+            }
+            self.accumulator -= TICK_TIME;
+            self.game_loop();
+            ticks += 1;
+        }
+        ticks as u16
+    }
+
+    pub fn game_loop(&mut self) {
+        self.tick += 1;
+        self.info.tick = self.tick;
+        self.event(TICK_TIME);
+        self.orders(TICK_TIME);
+        self.weapons(TICK_TIME);
+        self.energy(TICK_TIME);
+        self.publish_updates();
+        self.forget_commands();
     }
 
     pub fn add_event(&mut self, event: Event) {
-        self.events.push_front(event);
+        self.events.push_back(event);
     }
 
-    pub fn radar_scan(&self, owner: &String, only_enemies: bool) -> Vec<(f64, f64)> {
-        let mut radars = vec![];
-        for (_, obj) in self.objects.iter() {
-            if obj.check_owner(Some(owner)) {
-                radars.push(((obj.x, obj.y), obj.radar_radius));
+    // Регистрирует новую команду игрока в статусе Queued
+    pub fn new_command(&mut self, owner: &String) -> CommandId {
+        let id = self.next_command;
+        self.next_command += 1;
+        self.commands
+            .insert(id,
+                    Command {
+                        owner: owner.clone(),
+                        state: CommandState::Queued,
+                        object: None,
+                    });
+        id
+    }
+
+    pub fn get_command(&self, id: CommandId, owner: &String) -> Result<&Command, ServerError> {
+        match self.commands.get(&id) {
+            Some(command) => {
+                if command.owner.eq(owner) {
+                    Ok(command)
+                } else {
+                    Err(ServerError::CommandNotFound(id))
+                }
             }
+            None => Err(ServerError::CommandNotFound(id)),
         }
-        let mut result_vec = vec![];
-        for (_, obj) in self.objects.iter() {
-            if only_enemies && !obj.check_owner(Some(owner)) {
-                result_vec.push((obj.x, obj.y));
+    }
+
+    fn set_command_state(&mut self, id: CommandId, state: CommandState) {
+        if let Some(command) = self.commands.get_mut(&id) {
+            if command.state.is_active() && !state.is_active() {
+                self.finished_commands.push_back((id, self.tick));
             }
+            command.state = state;
         }
-        result_vec
     }
 
-    fn event(&mut self, elapsed: f64) {
-        let event = match self.events.pop_back() {
-            Some(e) => e,
-            None => return,
-        };
-        let return_event: Option<Event> = match event {
-            Event::MoveRequest(m_e) => {
-                if self.check_object_exsists(&m_e.name, Some(&m_e.owner)) {
-                    Some(Event::Move(MoveEvent {
-                                         name: m_e.name,
-                                         dest_x: m_e.dest_x,
-                                         dest_y: m_e.dest_y,
-                                     }))
-                } else {
-                    None
-                }
+    // Удаляет команды, завершённые больше COMMAND_RETENTION тиков назад,
+    // после этого /command_status отвечает CommandNotFound
+    fn forget_commands(&mut self) {
+        while let Some(&(id, tick)) = self.finished_commands.front() {
+            if self.tick - tick <= COMMAND_RETENTION {
+                break;
             }
+            self.finished_commands.pop_front();
+            self.commands.remove(&id);
+        }
+    }
 
-            Event::FireRequest(f_e) => {
-                if let Some(object) = self.get_object_mut(&f_e.name, Some(&f_e.owner)) {
-                    if object.cargo_remove(1.0) {
-                        Some(Event::Damage(DamageEvent {
-                                               x: f_e.dest_x,
-                                               y: f_e.dest_y,
-                                               size: object.weapon_radius,
-                                               d_type: object.weapon_type.clone(),
-                                               damage: 10.0,
-                                           }))
-                    } else {
-                        None
+    // Свои объекты и всё, что попадает в радиусы их радаров,
+    // вместе с лучшим типом радара, который видит объект
+    pub fn detected_objects(&self, owner: &String) -> Vec<(&SampleObject, RadarType)> {
+        let mut detected: HashMap<ObjectId, RadarType> = HashMap::new();
+        for obj in self.objects.values().filter(|obj| obj.check_owner(Some(owner))) {
+            detected.insert(obj.id, RadarType::Military);
+            let energy = self.components.energy(obj.id);
+            for radar in self.components.radars.get(obj.id).iter() {
+                if !radar.online(energy) {
+                    continue;
+                }
+                for id in self.spatial.query(obj.x, obj.y, radar.radius) {
+                    let best = detected.entry(*id).or_insert(RadarType::None);
+                    if *best < radar.rtype {
+                        *best = radar.rtype.clone();
                     }
-                } else {
-                    None
                 }
             }
-            Event::BuildRequest(b_e) => {
-                if self.check_object_exsists(&b_e.name, Some(&b_e.owner)) {
-                    Some(Event::Build(BuildEvent {
-                                          name: b_e.name,
-                                          b_name: b_e.b_name,
-                                          b_type: b_e.b_type.clone(),
-                                          speed: 0.1,
-                                          progress: 0.0,
-                                          max_progress: 100.0,
-                                      }))
-                } else {
-                    None
-                }
+        }
+        detected
+            .into_iter()
+            .filter_map(|(id, radar_type)| self.objects.get(&id).map(|obj| (obj, radar_type)))
+            .collect()
+    }
+
+    // Свои объекты и опознанные радаром не хуже Middle: только про них можно
+    // сообщить номер, тип и владельца. Остальные контакты есть только в /radar
+    pub fn visible_objects(&self, owner: &String) -> Vec<&SampleObject> {
+        self.detected_objects(owner)
+            .into_iter()
+            .filter(|&(_, ref radar_type)| *radar_type >= RadarType::Middle)
+            .map(|(obj, _)| obj)
+            .collect()
+    }
+
+    // Что показывают игроку /objects и /updates, администратору - весь мир
+    pub fn objects_for(&self, viewer: &String) -> Vec<ObjectResponse> {
+        if viewer == ADMIN {
+            self.objects.values().map(|obj| obj.to_response()).collect()
+        } else {
+            self.visible_objects(viewer)
+                .iter()
+                .map(|obj| obj.to_response())
+                .collect()
+        }
+    }
+
+    // Заводит игроку историю /updates при первом запросе
+    pub fn subscribe_updates(&mut self, viewer: &String) {
+        if !self.updates.subscribed(viewer) {
+            let objects = self.objects_for(viewer);
+            self.updates.subscribe(viewer, objects);
+        }
+    }
+
+    fn publish_updates(&mut self) {
+        let visible = self.updates
+            .viewers()
+            .into_iter()
+            .map(|viewer| {
+                     let objects = self.objects_for(&viewer);
+                     (viewer, objects)
+                 })
+            .collect();
+        self.updates.publish(visible);
+    }
+
+    pub fn radar_scan(&self, owner: &String, only_enemies: bool) -> Vec<ContactResponse> {
+        self.detected_objects(owner)
+            .iter()
+            .filter(|&&(obj, _)| !only_enemies || !obj.check_owner(Some(owner)))
+            .map(|&(obj, ref radar_type)| self.contact(obj, radar_type))
+            .collect()
+    }
+
+    // Что видно об объекте радару данного типа
+    fn contact(&self, object: &SampleObject, radar: &RadarType) -> ContactResponse {
+        let mut contact = ContactResponse {
+            x: object.x,
+            y: object.y,
+            id: None,
+            otype: None,
+            owner: None,
+            shell_health: None,
+            cargo_current: None,
+            orders: None,
+        };
+        if *radar >= RadarType::Middle {
+            contact.id = Some(object.id);
+            contact.otype = Some(object.otype.clone());
+            contact.owner = Some(object.owner.clone());
+        }
+        if *radar >= RadarType::Military {
+            let (dest_x, dest_y) = self.components
+                .drives
+                .first(object.id)
+                .map_or((object.x, object.y), |drive| (drive.dest_x, drive.dest_y));
+            // Цель показывается по первому стреляющему оружию
+            let weapons = self.components.weapons.get(object.id);
+            let weapon = weapons
+                .iter()
+                .find(|weapon| weapon.active)
+                .or_else(|| weapons.first());
+            contact.shell_health = self.components
+                .shells
+                .first(object.id)
+                .map(|shell| shell.health);
+            contact.cargo_current = Some(self.components
+                                             .cargo
+                                             .get(object.id)
+                                             .iter()
+                                             .map(|cargo| cargo.current)
+                                             .sum());
+            contact.orders = Some(OrdersResponse {
+                                      drive_dest_x: dest_x,
+                                      drive_dest_y: dest_y,
+                                      weapon_active: weapon.map_or(false, |w| w.active),
+                                      weapon_target_x: weapon.map_or(0.0, |w| w.target_x),
+                                      weapon_target_y: weapon.map_or(0.0, |w| w.target_y),
+                                      order: object.current_order().cloned(),
+                                  });
+        }
+        contact
+    }
+
+    // За тик обрабатываются все события, накопленные к его началу, в порядке
+    // добавления. Продолжения событий и порождённые ими новые события
+    // попадают в очередь следующего тика в том порядке, в котором появились
+    fn event(&mut self, elapsed: f64) {
+        let current: Vec<Event> = self.events.drain(..).collect();
+        for event in current {
+            if let Some(ev) = self.handle_event(event, elapsed) {
+                self.add_event(ev);
             }
-            Event::Move(m_e) => {
-                if let Some(object) = self.get_object_mut(&m_e.name, None) {
-                    if distance(object.x, object.y, object.drive_dest_x, object.drive_dest_y) <
-                       object.drive_speed * elapsed {
-                        object.x = object.drive_dest_x;
-                        object.y = object.drive_dest_y;
-                        None
-                    } else {
-                        if !((object.x - m_e.dest_x).abs() < ::FLOAT_ERR) {
-                            if object.x < m_e.dest_x {
-                                object.x += object.drive_speed * elapsed;
-                            } else if object.x > m_e.dest_x {
-                                object.x -= object.drive_speed * elapsed;
+        }
+    }
+
+    // Обрабатывает событие и возвращает его продолжение для следующего тика
+    fn handle_event(&mut self, event: Event, elapsed: f64) -> Option<Event> {
+        match event {
+            Event::OrderRequest(o_e) => {
+                self.limits.command_handled(&o_e.owner);
+                match self.get_owned_object(o_e.id, &o_e.owner).map(|_| ()) {
+                    Ok(_) => {
+                        let mut order = o_e.order;
+                        if !o_e.append {
+                            self.cancel_queue(o_e.id, &order.kind.queue());
+                        }
+                        // Постройка оплачивается при постановке в очередь
+                        if order.kind == OrderType::Build {
+                            let definition = order.b_type
+                                .as_ref()
+                                .and_then(|b_type| self.units.get(b_type))
+                                .map(|unit| (unit.build_cost, unit.build_time));
+                            match definition {
+                                Some((cost, time)) => {
+                                    order.b_cost = cost;
+                                    order.b_time = time;
+                                }
+                                None => {
+                                    let b_type = order.b_type.unwrap_or_default();
+                                    let error = ServerError::UnknownObjectType(b_type);
+                                    self.set_command_state(o_e.command,
+                                                           CommandState::Rejected(error));
+                                    return None;
+                                }
+                            }
+                            if self.get_resources(&o_e.owner) < order.b_cost {
+                                let error = ServerError::InsufficientResources(o_e.owner);
+                                self.set_command_state(o_e.command, CommandState::Rejected(error));
+                                return None;
                             }
+                            *self.resources.entry(o_e.owner).or_insert(0.0) -= order.b_cost;
                         }
-                        if !((object.y - m_e.dest_y).abs() < ::FLOAT_ERR) {
-                            if object.y < m_e.dest_y {
-                                object.y += object.drive_speed * elapsed;
-                            } else if object.y > m_e.dest_y {
-                                object.y -= object.drive_speed * elapsed;
+                        if let Some(object) = self.get_object_mut(o_e.id, None) {
+                            // Патрулирование идёт между целью и местом получения приказа
+                            if order.kind == OrderType::Patrol {
+                                order.from_x = object.x;
+                                order.from_y = object.y;
                             }
+                            object.queue_mut(&order.kind.queue()).push_back(order);
                         }
-                        Some(Event::Move(m_e))
                     }
-                } else {
-                    None
+                    Err(e) => self.set_command_state(o_e.command, CommandState::Rejected(e)),
+                }
+                None
+            }
+            Event::CancelRequest(c_e) => {
+                match self.get_owned_object(c_e.id, &c_e.owner).map(|_| ()) {
+                    Ok(_) => {
+                        self.cancel_orders(c_e.id);
+                        self.drive_stop(c_e.id);
+                        self.set_command_state(c_e.command, CommandState::Completed);
+                    }
+                    Err(e) => self.set_command_state(c_e.command, CommandState::Rejected(e)),
+                }
+                None
+            }
+            Event::WeaponStopRequest(s_e) => {
+                match self.get_owned_object(s_e.id, &s_e.owner).map(|_| ()) {
+                    Ok(_) => {
+                        self.weapons_stop(s_e.id);
+                        self.set_command_state(s_e.command, CommandState::Completed);
+                    }
+                    Err(e) => self.set_command_state(s_e.command, CommandState::Rejected(e)),
                 }
+                None
             }
             Event::Destroy(d_e) => {
-                if self.check_object_exsists(&d_e.name, None) {
-                    self.objects.remove(&d_e.name);
+                if self.check_object_exsists(d_e.id, None) {
+                    // Приказы уничтоженного объекта уже не будут выполнены
+                    self.cancel_orders(d_e.id);
+                    self.remove_object(d_e.id);
+                    self.updates.push_event("Destroyed", d_e.id);
                 }
                 None
             }
             Event::Damage(d_e) => {
-                let mut events = vec![];
-                for i in self.objects.iter_mut() {
-                    let (_, mut object) = i;
-
-                    if distance(object.x, object.y, d_e.x, d_e.y) <= d_e.size {
-                        object.shell_damage(d_e.d_type.clone(), d_e.damage);
-                        if object.shell_health <= 0.0 {
-                            events.push(Event::Destroy(DestroyEvent { name: object.name.clone() }));
+                // Порядок обхода индекса случаен, а очередь должна быть детерминированной
+                let mut hit: Vec<ObjectId> = self.spatial
+                    .query(d_e.x, d_e.y, d_e.size)
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                hit.sort();
+                let mut ore = 0.0;
+                for id in hit {
+                    // Объект без корпуса урона не получает
+                    let asteroid = self.objects
+                        .get(&id)
+                        .map_or(false, |obj| obj.otype.is_asteroid());
+                    let destroyed = match self.components.shells.first_mut(id) {
+                        Some(shell) => {
+                            let health = shell.health.max(0.0);
+                            shell.damage(&self.damage, &d_e.d_type, d_e.damage);
+                            if d_e.d_type.is_mining() && asteroid {
+                                ore += health - shell.health.max(0.0);
+                            }
+                            shell.health <= 0.0
                         }
+                        None => false,
+                    };
+                    if destroyed {
+                        self.add_event(Event::Destroy(DestroyEvent { id: id }));
+                    }
+                }
+                // Добытая руда попадает в трюм стрелявшего, сколько поместится
+                if let Some(source) = d_e.source {
+                    if let Some(cargo) = self.components.mining_cargo(source) {
+                        let space = cargo.space();
+                        cargo.add(ore.min(space));
                     }
                 }
-                for ev in events {
+                None
+            }
+            Event::Batch(events) => {
+                let next: Vec<Event> = events
+                    .into_iter()
+                    .filter_map(|ev| self.handle_event(ev, elapsed))
+                    .collect();
+                for ev in next {
                     self.add_event(ev);
                 }
                 None
             }
-            Event::Build(b_e) => {
-                if b_e.progress >= b_e.max_progress {
-                    let result_object = {
-                        if let Some(object) = self.get_object(&b_e.name, None) {
-                            Some(SampleObject::new(object.owner.clone(),
-                                                   b_e.b_name,
-                                                   b_e.b_type,
-                                                   object.x,
-                                                   object.y))
-                        } else {
-                            None
+        }
+    }
+
+    // Снимает все приказы и заказы объекта
+    fn cancel_orders(&mut self, id: ObjectId) {
+        self.cancel_queue(id, &OrderQueue::Orders);
+        self.cancel_queue(id, &OrderQueue::Production);
+    }
+
+    // Команды снятых приказов получают статус Cancelled,
+    // за неначатые и недостроенные постройки ресурсы возвращаются.
+    // Вместе с приказами прекращается и стрельба по ним
+    fn cancel_queue(&mut self, id: ObjectId, queue: &OrderQueue) {
+        let (owner, cancelled): (String, Vec<Order>) = match self.objects.get_mut(&id) {
+            Some(object) => (object.owner.clone(), object.queue_mut(queue).drain(..).collect()),
+            None => return,
+        };
+        if *queue == OrderQueue::Orders {
+            self.weapons_stop(id);
+        }
+        for order in cancelled {
+            if order.kind == OrderType::Build {
+                *self.resources.entry(owner.clone()).or_insert(0.0) += order.b_cost;
+            }
+            self.set_command_state(order.command, CommandState::Cancelled);
+        }
+    }
+
+    // Каждый объект выполняет первый приказ из очереди приказов и первый
+    // заказ из очереди производства. Объекты обходятся в порядке номеров,
+    // после обработки событий тика
+    fn orders(&mut self, elapsed: f64) {
+        self.run_queue(&OrderQueue::Orders, elapsed);
+        self.run_queue(&OrderQueue::Production, elapsed);
+    }
+
+    fn run_queue(&mut self, queue: &OrderQueue, elapsed: f64) {
+        let mut ids: Vec<ObjectId> = self.objects
+            .values()
+            .filter(|obj| !obj.queue(queue).is_empty())
+            .map(|obj| obj.id)
+            .collect();
+        ids.sort();
+        for id in ids {
+            let mut order = match self.objects
+                      .get(&id)
+                      .and_then(|obj| obj.queue(queue).front().cloned()) {
+                Some(order) => order,
+                None => continue,
+            };
+            self.set_command_state(order.command, CommandState::InProgress);
+            let result = self.execute_order(id, &mut order, elapsed);
+            if let Some(object) = self.objects.get_mut(&id) {
+                self.spatial.insert(id, object.x, object.y);
+                match result {
+                    OrderResult::Continue => {
+                        if let Some(current) = object.queue_mut(queue).front_mut() {
+                            *current = order.clone();
                         }
+                    }
+                    OrderResult::Done |
+                    OrderResult::Failed(_) => {
+                        object.queue_mut(queue).pop_front();
+                    }
+                }
+            }
+            match result {
+                OrderResult::Continue => {}
+                OrderResult::Done => self.set_command_state(order.command, CommandState::Completed),
+                OrderResult::Failed(e) => {
+                    self.set_command_state(order.command, CommandState::Rejected(e))
+                }
+            }
+        }
+    }
+
+    // Включённое оружие стреляет по своей цели, пока его не выключат,
+    // независимо от приказов. Каждое оружие объекта перезаряжается и тратит
+    // энергию отдельно. Урон наносится событием следующего тика
+    fn weapons(&mut self, elapsed: f64) {
+        let ids: Vec<ObjectId> = self.components.weapons.iter().map(|(id, _)| *id).collect();
+        let mut shots = vec![];
+        for id in ids {
+            let object = match self.objects.get(&id) {
+                Some(object) => object,
+                None => continue,
+            };
+            for index in 0..self.components.weapons.get(id).len() {
+                let cost = {
+                    let weapon = &mut self.components.weapons.get_mut(id)[index];
+                    if !weapon.reload(elapsed) ||
+                       !weapon.in_range(object, weapon.target_x, weapon.target_y) {
+                        continue;
+                    }
+                    weapon.energy
+                };
+                if !self.components.energy_use(id, cost) {
+                    self.components.weapons.get_mut(id)[index].stop();
+                    continue;
+                }
+                let weapon = &mut self.components.weapons.get_mut(id)[index];
+                weapon.shot();
+                shots.push(DamageEvent {
+                               x: weapon.target_x,
+                               y: weapon.target_y,
+                               size: weapon.radius,
+                               d_type: weapon.wtype.clone(),
+                               damage: weapon.damage,
+                               source: Some(id),
+                           });
+            }
+        }
+        for shot in shots {
+            self.add_event(Event::Damage(shot));
+        }
+    }
+
+    // Генерация батарей и расход радаров, затем зарядка от батарей с
+    // charge_rate: всех своих объектов в charge_radius и всех пристыкованных.
+    // Объекты обходятся в порядке номеров
+    fn energy(&mut self, elapsed: f64) {
+        for (_, batteries) in self.components.batteries.iter_mut() {
+            for battery in batteries.iter_mut() {
+                let generated = battery.generation * elapsed;
+                battery.add(generated);
+            }
+        }
+
+        let ids: Vec<ObjectId> = self.components.radars.iter().map(|(id, _)| *id).collect();
+        for id in ids {
+            let drain: f64 = self.components
+                .radars
+                .get(id)
+                .iter()
+                .filter(|radar| radar.rtype != RadarType::None)
+                .map(|radar| radar.energy * elapsed)
+                .sum();
+            if !self.components.energy_use(id, drain) {
+                for battery in self.components.batteries.get_mut(id).iter_mut() {
+                    battery.current = 0.0;
+                }
+            }
+        }
+
+        let mut chargers = vec![];
+        for (id, batteries) in self.components.batteries.iter() {
+            for (index, battery) in batteries.iter().enumerate() {
+                if battery.charge_rate > 0.0 {
+                    chargers.push((*id, index));
+                }
+            }
+        }
+        for (id, index) in chargers {
+            let (radius, rate) = {
+                let battery = &self.components.batteries.get(id)[index];
+                (battery.charge_radius.max(DOCK_RADIUS), battery.charge_rate)
+            };
+            let mut targets: Vec<ObjectId> = match self.objects.get(&id) {
+                Some(source) => {
+                    self.objects_in_radius(source.x, source.y, radius)
+                        .iter()
+                        .filter(|obj| {
+                                    obj.id != id && obj.owner == source.owner &&
+                                    !self.components.energy_full(obj.id)
+                                })
+                        .map(|obj| obj.id)
+                        .collect()
+                }
+                None => continue,
+            };
+            targets.sort();
+            for target in targets {
+                let available = self.components.batteries.get(id)[index].current;
+                let given = self.components.energy_add(target, (rate * elapsed).min(available));
+                self.components.batteries.get_mut(id)[index].take(given);
+            }
+        }
+    }
+
+    fn execute_order(&mut self, id: ObjectId, order: &mut Order, elapsed: f64) -> OrderResult {
+        let (owner, x, y) = match self.objects.get(&id) {
+            Some(object) => (object.owner.clone(), object.x, object.y),
+            None => return OrderResult::Failed(ServerError::ObjectNotFound(id)),
+        };
+
+        match order.kind {
+            OrderType::Move => {
+                match self.drive_update(id, order.x, order.y, elapsed) {
+                    Ok(true) => OrderResult::Done,
+                    Ok(false) => OrderResult::Continue,
+                    Err(e) => OrderResult::Failed(e),
+                }
+            }
+            OrderType::Patrol => {
+                match self.drive_update(id, order.x, order.y, elapsed) {
+                    Ok(true) => {
+                        let (x, y) = (order.x, order.y);
+                        order.x = order.from_x;
+                        order.y = order.from_y;
+                        order.from_x = x;
+                        order.from_y = y;
+                        OrderResult::Continue
+                    }
+                    Ok(false) => OrderResult::Continue,
+                    Err(e) => OrderResult::Failed(e),
+                }
+            }
+            OrderType::Hold => {
+                self.drive_stop(id);
+                OrderResult::Continue
+            }
+            OrderType::Attack => {
+                self.weapon_order(id, order, elapsed, false)
+                    .unwrap_or(OrderResult::Continue)
+            }
+            // Добыча идёт, пока в точке есть астероиды и есть место в трюме
+            OrderType::Harvest => {
+                let full = match self.components.mining_cargo(id) {
+                    Some(cargo) => cargo.space() <= 0.0,
+                    None => return OrderResult::Failed(ServerError::NoMiningCargo(id)),
+                };
+                if full {
+                    self.weapons_stop(id);
+                    return OrderResult::Done;
+                }
+                if let Some(result) = self.weapon_order(id, order, elapsed, true) {
+                    return result;
+                }
+                let radius = self.components
+                    .weapons
+                    .get(id)
+                    .iter()
+                    .filter(|weapon| weapon.wtype.is_mining())
+                    .fold(0.0, |radius, weapon| weapon.radius.max(radius));
+                let asteroids = self.objects_in_radius(order.x, order.y, radius)
+                    .iter()
+                    .any(|obj| obj.otype.is_asteroid());
+                if !asteroids {
+                    self.weapons_stop(id);
+                    return OrderResult::Done;
+                }
+                OrderResult::Continue
+            }
+            OrderType::Unload => {
+                self.drive_stop(id);
+                let builder = self.objects_in_radius(x, y, DOCK_RADIUS)
+                    .iter()
+                    .any(|obj| obj.owner == owner && obj.otype.is_builder());
+                if !builder {
+                    return OrderResult::Failed(ServerError::NoBuilderNearby(id));
+                }
+                let cargo = match self.components.mining_cargo(id) {
+                    Some(cargo) => {
+                        let current = cargo.current;
+                        cargo.remove(current);
+                        current
+                    }
+                    None => 0.0,
+                };
+                *self.resources.entry(owner).or_insert(0.0) += cargo;
+                OrderResult::Done
+            }
+            OrderType::Build => {
+                if order.progress < order.b_time {
+                    order.progress += elapsed;
+                    return OrderResult::Continue;
+                }
+                if let (Some(b_type), Some(b_name)) = (order.b_type.clone(), order.b_name.clone()) {
+                    let built = match self.add_object(b_name, x, y, b_type, owner) {
+                        Ok(built) => built,
+                        Err(e) => return OrderResult::Failed(e),
                     };
-                    if let Some(object) = result_object {
-                        self.objects.insert(object.name.clone(), object).unwrap();
+                    self.updates.push_event("Built", built);
+                    if let Some(command) = self.commands.get_mut(&order.command) {
+                        command.object = Some(built);
                     }
-                    None
-                } else {
-                    let mut new_event = b_e.clone();
-                    new_event.progress += b_e.speed * elapsed;
-                    Some(Event::Build(new_event))
+                }
+                OrderResult::Done
+            }
+        }
+    }
+
+    // Общая часть Attack и Harvest: подойти на дальность выбранного оружия и
+    // открыть огонь из всех его стволов, для Harvest только из добывающих.
+    // progress - сколько времени идёт стрельба. Приказ завершается, когда всё
+    // выбранное оружие выключено через /weapon_stop или из-за нехватки энергии.
+    // None - огонь ведётся
+    fn weapon_order(&mut self,
+                    id: ObjectId,
+                    order: &mut Order,
+                    elapsed: f64,
+                    mining: bool)
+                    -> Option<OrderResult> {
+        let (active, cost, in_range) = {
+            let object = match self.objects.get(&id) {
+                Some(object) => object,
+                None => return Some(OrderResult::Failed(ServerError::ObjectNotFound(id))),
+            };
+            let weapons: Vec<&Weapon> = self.components
+                .weapons
+                .get(id)
+                .iter()
+                .filter(|weapon| !mining || weapon.wtype.is_mining())
+                .collect();
+            if weapons.is_empty() {
+                return Some(OrderResult::Failed(ServerError::NoWeapon(id)));
+            }
+            (weapons.iter().any(|weapon| weapon.active),
+             weapons.iter().fold(f64::INFINITY, |cost, weapon| weapon.energy.min(cost)),
+             weapons.iter().all(|weapon| weapon.in_range(object, order.x, order.y)))
+        };
+        if order.progress > 0.0 && !active {
+            return Some(if self.components.energy(id) < cost {
+                            OrderResult::Failed(ServerError::InsufficientEnergy(id))
+                        } else {
+                            OrderResult::Done
+                        });
+        }
+        if !in_range {
+            if !self.components.can_move(id) {
+                return Some(OrderResult::Failed(ServerError::OutOfRange(id)));
+            }
+            return Some(match self.drive_update(id, order.x, order.y, elapsed) {
+                            Ok(_) => OrderResult::Continue,
+                            Err(e) => OrderResult::Failed(e),
+                        });
+        }
+        self.drive_stop(id);
+        if order.progress == 0.0 {
+            for weapon in self.components.weapons.get_mut(id).iter_mut() {
+                if !mining || weapon.wtype.is_mining() {
+                    weapon.fire(order.x, order.y);
                 }
             }
+        }
+        order.progress += elapsed;
+        None
+    }
+
+    // Шаг движения к точке за счёт батареи. Возвращает true, когда объект
+    // прибыл. Без заряда объект останавливается и приказ не выполнить
+    fn drive_update(&mut self,
+                    id: ObjectId,
+                    x: f64,
+                    y: f64,
+                    elapsed: f64)
+                    -> Result<bool, ServerError> {
+        let cost = match self.components.drives.first(id) {
+            Some(drive) => drive.energy * elapsed,
+            None => return Err(ServerError::NoDrive(id)),
         };
-        if let Some(ev) = return_event {
-            self.add_event(ev);
+        let powered = self.components.energy_use(id, cost);
+        match (self.objects.get_mut(&id), self.components.drives.first_mut(id)) {
+            (Some(object), Some(drive)) => {
+                drive.move_to(x, y);
+                if powered {
+                    Ok(drive.update(object, elapsed))
+                } else if drive.arrived(object) {
+                    Ok(true)
+                } else {
+                    drive.speed = 0.0;
+                    Err(ServerError::InsufficientEnergy(id))
+                }
+            }
+            _ => Err(ServerError::ObjectNotFound(id)),
+        }
+    }
+
+    fn drive_stop(&mut self, id: ObjectId) {
+        if let (Some(object), Some(drive)) = (self.objects.get(&id),
+                                              self.components.drives.first_mut(id)) {
+            drive.stop(object);
+        }
+    }
+
+    fn weapons_stop(&mut self, id: ObjectId) {
+        for weapon in self.components.weapons.get_mut(id).iter_mut() {
+            weapon.stop();
         }
     }
 }
 
+// XorShift не принимает нулевой seed, поэтому добавляем константы
+pub fn seeded_rng(seed: u64) -> XorShiftRng {
+    XorShiftRng::from_seed([seed as u32, (seed >> 32) as u32, 0x9E37_79B9, 0x7F4A_7C15])
+}
+
 pub fn distance(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
     ((x1 - x2).powf(2.0) + (y1 - y2).powf(2.0)).sqrt()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use data_types::*;
+    use server::config::GameConfig;
+    use server::damage::DamageTable;
+    use server::units::UnitRegistry;
+    use server::events::*;
+    use level_generator::generate;
+    use super::GameEngine;
+
+    const PLAYER: &'static str = "Player0";
+
+    fn engine(seed: u64) -> GameEngine {
+        let config = GameConfig::new("config/engine.json").unwrap();
+        let damage = DamageTable::new(&config.damage_table).unwrap();
+        let units = UnitRegistry::new(&config.units_dir, &damage).unwrap();
+        GameEngine::new(1000.0, 1000.0, config, damage, units, seed)
+    }
+
+    fn spawn(engine: &mut GameEngine, otype: &str, owner: &str, x: f64, y: f64) -> ObjectId {
+        engine
+            .add_object(otype.to_owned(),
+                        x,
+                        y,
+                        ObjectType(otype.to_owned()),
+                        owner.to_owned())
+            .unwrap()
+    }
+
+    fn new_order(kind: OrderType, x: f64, y: f64) -> Order {
+        Order {
+            command: 0,
+            kind: kind,
+            x: x,
+            y: y,
+            from_x: x,
+            from_y: y,
+            b_type: None,
+            b_name: None,
+            b_cost: 0.0,
+            b_time: 0.0,
+            progress: 0.0,
+        }
+    }
+
+    fn build_order(otype: &str) -> Order {
+        let mut order = new_order(OrderType::Build, 0.0, 0.0);
+        order.b_type = Some(ObjectType(otype.to_owned()));
+        order.b_name = Some(format!("{}1", otype));
+        order
+    }
+
+    // Ставит приказ в очередь событий так же, как это делают запросы
+    fn give(engine: &mut GameEngine, id: ObjectId, mut order: Order, append: bool) -> CommandId {
+        let owner = engine.objects[&id].owner.clone();
+        let command = engine.new_command(&owner);
+        order.command = command;
+        engine.add_event(Event::OrderRequest(NetworkOrderEvent {
+                                                 command: command,
+                                                 id: id,
+                                                 owner: owner,
+                                                 order: order,
+                                                 append: append,
+                                             }));
+        command
+    }
+
+    fn cancel(engine: &mut GameEngine, id: ObjectId) -> CommandId {
+        let owner = engine.objects[&id].owner.clone();
+        let command = engine.new_command(&owner);
+        engine.add_event(Event::CancelRequest(NetworkCancelEvent {
+                                                  command: command,
+                                                  id: id,
+                                                  owner: owner,
+                                              }));
+        command
+    }
+
+    fn status(engine: &GameEngine, command: CommandId) -> &'static str {
+        match engine.commands[&command].state {
+            CommandState::Queued => "Queued",
+            CommandState::Rejected(_) => "Rejected",
+            CommandState::InProgress => "InProgress",
+            CommandState::Completed => "Completed",
+            CommandState::Cancelled => "Cancelled",
+        }
+    }
+
+    fn cargo(engine: &GameEngine, id: ObjectId) -> f64 {
+        engine.components.cargo.first(id).unwrap().current
+    }
+
+    fn set_energy(engine: &mut GameEngine, id: ObjectId, current: f64) {
+        engine.components.batteries.get_mut(id)[0].current = current;
+    }
+
+    fn assert_near(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-6, "{} != {}", value, expected);
+    }
+
+    fn health(engine: &GameEngine, id: ObjectId) -> f64 {
+        engine.components.shells.first(id).unwrap().health
+    }
+
+    fn run(engine: &mut GameEngine, ticks: usize) {
+        for _ in 0..ticks {
+            engine.game_loop();
+        }
+    }
+
+    #[test]
+    fn same_seed_and_commands_give_same_world() {
+        let players = vec![PLAYER.to_owned(), "Player1".to_owned()];
+        let mut worlds = vec![];
+        for _ in 0..2 {
+            let mutex = Arc::new(Mutex::new(engine(42)));
+            generate(mutex.clone(), 1000.0, 1000.0, players.clone());
+            let mut engine = mutex.lock().unwrap();
+            let mut bases: Vec<ObjectId> = engine
+                .objects
+                .values()
+                .filter(|obj| obj.otype.is_builder())
+                .map(|obj| obj.id)
+                .collect();
+            bases.sort();
+            for (i, base) in bases.into_iter().enumerate() {
+                give(&mut engine, base, new_order(OrderType::Move, 300.0, 50.0 * i as f64), false);
+                give(&mut engine, base, new_order(OrderType::Move, 0.0, 0.0), true);
+            }
+            run(&mut engine, 600);
+            worlds.push((engine.get_objects(), engine.resources.clone(), engine.tick));
+        }
+        assert!(!worlds[0].0.is_empty());
+        assert!(worlds[0] == worlds[1]);
+    }
+
+    #[test]
+    fn new_order_replaces_queue_unless_appended() {
+        let mut engine = engine(1);
+        let base = spawn(&mut engine, "Builder", PLAYER, 100.0, 100.0);
+        let first = give(&mut engine, base, new_order(OrderType::Move, 200.0, 100.0), false);
+        let second = give(&mut engine, base, new_order(OrderType::Move, 300.0, 100.0), true);
+        run(&mut engine, 1);
+        assert_eq!(engine.objects[&base].orders.len(), 2);
+        assert_eq!(status(&engine, first), "InProgress");
+        assert_eq!(status(&engine, second), "Queued");
+
+        let third = give(&mut engine, base, new_order(OrderType::Move, 0.0, 0.0), false);
+        run(&mut engine, 1);
+        assert_eq!(engine.objects[&base].orders.len(), 1);
+        assert_eq!(status(&engine, first), "Cancelled");
+        assert_eq!(status(&engine, second), "Cancelled");
+        assert_eq!(status(&engine, third), "InProgress");
+    }
+
+    #[test]
+    fn appended_orders_run_one_after_another() {
+        let mut engine = engine(1);
+        let base = spawn(&mut engine, "Builder", PLAYER, 100.0, 100.0);
+        let first = give(&mut engine, base, new_order(OrderType::Move, 110.0, 100.0), false);
+        let second = give(&mut engine, base, new_order(OrderType::Move, 110.0, 110.0), true);
+        run(&mut engine, 60 * 60);
+        assert_eq!(status(&engine, first), "Completed");
+        assert_eq!(status(&engine, second), "Completed");
+        let base = &engine.objects[&base];
+        assert_eq!((base.x, base.y), (110.0, 110.0));
+    }
+
+    #[test]
+    fn cancel_clears_queues_and_refunds_builds() {
+        let mut engine = engine(1);
+        engine.resources.insert(PLAYER.to_owned(), 1000.0);
+        let base = spawn(&mut engine, "Builder", PLAYER, 100.0, 100.0);
+        let build = give(&mut engine, base, build_order("Harvester"), false);
+        let queued_build = give(&mut engine, base, build_order("Harvester"), true);
+        let movement = give(&mut engine, base, new_order(OrderType::Move, 300.0, 100.0), false);
+        run(&mut engine, 10);
+        assert_eq!(engine.get_resources(&PLAYER.to_owned()), 800.0);
+        assert_eq!(status(&engine, build), "InProgress");
+
+        let cancelled = cancel(&mut engine, base);
+        run(&mut engine, 1);
+        assert_eq!(status(&engine, cancelled), "Completed");
+        for command in [build, queued_build, movement].iter() {
+            assert_eq!(status(&engine, *command), "Cancelled");
+        }
+        assert_eq!(engine.get_resources(&PLAYER.to_owned()), 1000.0);
+        assert!(engine.objects[&base].orders.is_empty());
+        assert!(engine.objects[&base].production.is_empty());
+        assert_eq!(engine.components.drives.first(base).unwrap().speed, 0.0);
+    }
+
+    #[test]
+    fn cancel_of_foreign_object_is_rejected() {
+        let mut engine = engine(1);
+        let base = spawn(&mut engine, "Builder", PLAYER, 100.0, 100.0);
+        let movement = give(&mut engine, base, new_order(OrderType::Move, 300.0, 100.0), false);
+        let command = engine.new_command(&"Player1".to_owned());
+        engine.add_event(Event::CancelRequest(NetworkCancelEvent {
+                                                  command: command,
+                                                  id: base,
+                                                  owner: "Player1".to_owned(),
+                                              }));
+        run(&mut engine, 1);
+        assert_eq!(status(&engine, command), "Rejected");
+        assert_eq!(status(&engine, movement), "InProgress");
+    }
+
+    #[test]
+    fn harvested_ore_fills_cargo_and_unloads_to_bank() {
+        let mut engine = engine(1);
+        spawn(&mut engine, "Builder", PLAYER, 100.0, 100.0);
+        let harvester = spawn(&mut engine, "Harvester", PLAYER, 105.0, 100.0);
+        let asteroid = spawn(&mut engine, "Asteroid", "unknown", 115.0, 100.0);
+        let harvest = give(&mut engine,
+                           harvester,
+                           new_order(OrderType::Harvest, 115.0, 100.0),
+                           false);
+        run(&mut engine, 120);
+        // Вся снятая с астероида прочность попадает в трюм
+        let ore = cargo(&engine, harvester);
+        let health = engine.components.shells.first(asteroid).unwrap().health;
+        assert!(ore > 0.0);
+        assert_eq!(1000.0 - health, ore);
+
+        // Трюм полон - добыча закончена
+        run(&mut engine, 60 * 15);
+        assert_eq!(status(&engine, harvest), "Completed");
+        assert_eq!(cargo(&engine, harvester), 100.0);
+        assert!(engine
+                    .components
+                    .weapons
+                    .get(harvester)
+                    .iter()
+                    .all(|weapon| !weapon.active));
+
+        let unload = give(&mut engine, harvester, new_order(OrderType::Unload, 0.0, 0.0), false);
+        run(&mut engine, 1);
+        assert_eq!(status(&engine, unload), "Completed");
+        assert_eq!(cargo(&engine, harvester), 0.0);
+        assert_eq!(engine.get_resources(&PLAYER.to_owned()), 100.0);
+    }
+
+    #[test]
+    fn unload_needs_own_builder_nearby() {
+        let mut engine = engine(1);
+        spawn(&mut engine, "Builder", "Player1", 100.0, 100.0);
+        let harvester = spawn(&mut engine, "Harvester", PLAYER, 105.0, 100.0);
+        engine.components.mining_cargo(harvester).unwrap().add(50.0);
+        let unload = give(&mut engine, harvester, new_order(OrderType::Unload, 0.0, 0.0), false);
+        run(&mut engine, 1);
+        assert_eq!(status(&engine, unload), "Rejected");
+        assert_eq!(cargo(&engine, harvester), 50.0);
+        assert_eq!(engine.get_resources(&PLAYER.to_owned()), 0.0);
+    }
+
+    #[test]
+    fn radar_and_drive_drain_battery() {
+        let mut engine = engine(1);
+        let cruiser = spawn(&mut engine, "Battlecruiser", PLAYER, 100.0, 100.0);
+        // Стоящий корабль тратит только на радар
+        run(&mut engine, 60);
+        assert_near(engine.components.energy(cruiser), 99.5);
+
+        give(&mut engine, cruiser, new_order(OrderType::Move, 500.0, 100.0), false);
+        run(&mut engine, 60);
+        assert_near(engine.components.energy(cruiser), 98.0);
+    }
+
+    #[test]
+    fn empty_battery_rejects_move() {
+        let mut engine = engine(1);
+        let cruiser = spawn(&mut engine, "Battlecruiser", PLAYER, 100.0, 100.0);
+        set_energy(&mut engine, cruiser, 0.0);
+        let command = give(&mut engine, cruiser, new_order(OrderType::Move, 500.0, 100.0), false);
+        run(&mut engine, 1);
+        assert_eq!(status(&engine, command), "Rejected");
+        assert_eq!(engine.objects[&cruiser].x, 100.0);
+    }
+
+    #[test]
+    fn builder_recharges_own_units_nearby() {
+        let mut engine = engine(1);
+        let base = spawn(&mut engine, "Builder", PLAYER, 100.0, 100.0);
+        let own = spawn(&mut engine, "Harvester", PLAYER, 140.0, 100.0);
+        let foreign = spawn(&mut engine, "Harvester", "Player1", 130.0, 100.0);
+        let far = spawn(&mut engine, "Harvester", PLAYER, 200.0, 100.0);
+        set_energy(&mut engine, base, 500.0);
+        for &id in [own, foreign, far].iter() {
+            set_energy(&mut engine, id, 50.0);
+        }
+        run(&mut engine, 60);
+        // Генерация 20 в секунду, зарядка одного харвестера 10 в секунду
+        assert_near(engine.components.energy(base), 510.0);
+        assert_near(engine.components.energy(own), 59.9);
+        assert_near(engine.components.energy(foreign), 49.9);
+        assert_near(engine.components.energy(far), 49.9);
+    }
+
+    #[test]
+    fn weapon_fires_until_stopped_at_its_rate() {
+        let mut engine = engine(1);
+        let cruiser = spawn(&mut engine, "Battlecruiser", PLAYER, 100.0, 100.0);
+        let target = spawn(&mut engine, "Harvester", "Player1", 150.0, 100.0);
+        let attack = give(&mut engine, cruiser, new_order(OrderType::Attack, 150.0, 100.0), false);
+        // Выстрел раз в секунду, урон применяется на следующем тике
+        run(&mut engine, 1);
+        assert_eq!(health(&engine, target), 100.0);
+        run(&mut engine, 1);
+        assert_eq!(health(&engine, target), 90.0);
+        run(&mut engine, 148);
+        assert_eq!(health(&engine, target), 70.0);
+        assert_near(engine.components.energy(cruiser), 100.0 - 3.0 - 0.5 * 2.5);
+        assert_eq!(status(&engine, attack), "InProgress");
+
+        let owner = PLAYER.to_owned();
+        let stop = engine.new_command(&owner);
+        engine.add_event(Event::WeaponStopRequest(NetworkCancelEvent {
+                                                      command: stop,
+                                                      id: cruiser,
+                                                      owner: owner,
+                                                  }));
+        run(&mut engine, 1);
+        assert_eq!(status(&engine, stop), "Completed");
+        assert_eq!(status(&engine, attack), "Completed");
+        run(&mut engine, 120);
+        assert_eq!(health(&engine, target), 70.0);
+    }
+
+    #[test]
+    fn attack_moves_into_range_before_firing() {
+        let mut engine = engine(1);
+        let cruiser = spawn(&mut engine, "Battlecruiser", PLAYER, 100.0, 100.0);
+        let target = spawn(&mut engine, "Harvester", "Player1", 300.0, 100.0);
+        give(&mut engine, cruiser, new_order(OrderType::Attack, 300.0, 100.0), false);
+        run(&mut engine, 60);
+        assert_eq!(health(&engine, target), 100.0);
+        assert!(engine.objects[&cruiser].x > 100.0);
+
+        run(&mut engine, 60 * 20);
+        assert!(health(&engine, target) < 100.0);
+        assert!(300.0 - engine.objects[&cruiser].x <= 100.0);
+    }
+
+    #[test]
+    fn weapon_stops_without_energy() {
+        let mut engine = engine(1);
+        let cruiser = spawn(&mut engine, "Battlecruiser", PLAYER, 100.0, 100.0);
+        let target = spawn(&mut engine, "Harvester", "Player1", 150.0, 100.0);
+        set_energy(&mut engine, cruiser, 2.0);
+        let attack = give(&mut engine, cruiser, new_order(OrderType::Attack, 150.0, 100.0), false);
+        // На второй выстрел энергии не хватает из-за радара
+        run(&mut engine, 120);
+        assert_eq!(health(&engine, target), 90.0);
+        assert_eq!(status(&engine, attack), "Rejected");
+        assert!(engine
+                    .components
+                    .weapons
+                    .get(cruiser)
+                    .iter()
+                    .all(|weapon| !weapon.active));
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Размер ячейки сетки по умолчанию, порядка радиуса радара
+pub const DEFAULT_CELL_SIZE: f64 = 50.0;
+
+// Равномерная сетка для поиска объектов в радиусе. Хранит позицию каждого
+// ключа, поэтому после перемещения объекта достаточно снова вызвать insert
+pub struct SpatialGrid<K> {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<K>>,
+    positions: HashMap<K, (f64, f64)>,
+}
+
+impl<K: Hash + Eq + Clone> SpatialGrid<K> {
+    pub fn new(cell_size: f64) -> Self {
+        SpatialGrid {
+            cell_size: cell_size,
+            cells: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, x: f64, y: f64) -> (i64, i64) {
+        ((x / self.cell_size).floor() as i64, (y / self.cell_size).floor() as i64)
+    }
+
+    // Добавляет ключ или переносит его на новую позицию
+    pub fn insert(&mut self, key: K, x: f64, y: f64) {
+        let cell = self.cell(x, y);
+        if let Some(&(old_x, old_y)) = self.positions.get(&key) {
+            let old_cell = self.cell(old_x, old_y);
+            if old_cell != cell {
+                self.remove_from_cell(&key, old_cell);
+                self.cells.entry(cell).or_insert_with(Vec::new).push(key.clone());
+            }
+        } else {
+            self.cells.entry(cell).or_insert_with(Vec::new).push(key.clone());
+        }
+        self.positions.insert(key, (x, y));
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        if let Some((x, y)) = self.positions.remove(key) {
+            let cell = self.cell(x, y);
+            self.remove_from_cell(key, cell);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.positions.clear();
+    }
+
+    fn remove_from_cell(&mut self, key: &K, cell: (i64, i64)) {
+        let empty = match self.cells.get_mut(&cell) {
+            Some(keys) => {
+                if let Some(pos) = keys.iter().position(|k| k == key) {
+                    keys.swap_remove(pos);
+                }
+                keys.is_empty()
+            }
+            None => false,
+        };
+        if empty {
+            self.cells.remove(&cell);
+        }
+    }
+
+    // Ключи, позиции которых лежат не дальше radius от точки
+    pub fn query(&self, x: f64, y: f64, radius: f64) -> Vec<&K> {
+        let (min_x, min_y) = self.cell(x - radius, y - radius);
+        let (max_x, max_y) = self.cell(x + radius, y + radius);
+        let inside = |key: &&K| match self.positions.get(*key) {
+            Some(&(kx, ky)) => (kx - x).powf(2.0) + (ky - y).powf(2.0) <= radius * radius,
+            None => false,
+        };
+
+        // Для огромного радиуса быстрее пройти по занятым ячейкам, чем по всем в квадрате
+        let width = max_x.saturating_sub(min_x).saturating_add(1);
+        let height = max_y.saturating_sub(min_y).saturating_add(1);
+        if width.saturating_mul(height) > self.cells.len() as i64 {
+            return self.cells
+                       .iter()
+                       .filter(|&(&(cx, cy), _)| {
+                                   cx >= min_x && cx <= max_x && cy >= min_y && cy <= max_y
+                               })
+                       .flat_map(|(_, keys)| keys.iter())
+                       .filter(inside)
+                       .collect();
+        }
+        let mut result = vec![];
+        for cx in min_x..max_x + 1 {
+            for cy in min_y..max_y + 1 {
+                if let Some(keys) = self.cells.get(&(cx, cy)) {
+                    result.extend(keys.iter().filter(&inside));
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpatialGrid;
+
+    fn sorted(keys: Vec<&u64>) -> Vec<u64> {
+        let mut keys: Vec<u64> = keys.into_iter().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn query_returns_keys_inside_radius() {
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert(1, 0.0, 0.0);
+        grid.insert(2, 3.0, 4.0);
+        grid.insert(3, 6.0, 8.0);
+        grid.insert(4, -25.0, -25.0);
+        assert_eq!(sorted(grid.query(0.0, 0.0, 5.0)), vec![1, 2]);
+        assert_eq!(sorted(grid.query(0.0, 0.0, 10.0)), vec![1, 2, 3]);
+        assert_eq!(sorted(grid.query(-20.0, -20.0, 8.0)), vec![4]);
+        assert!(grid.query(100.0, 100.0, 5.0).is_empty());
+    }
+
+    #[test]
+    fn query_follows_moved_and_removed_keys() {
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert(1, 0.0, 0.0);
+        grid.insert(2, 1.0, 1.0);
+        grid.insert(1, 55.0, 55.0);
+        assert_eq!(sorted(grid.query(0.0, 0.0, 5.0)), vec![2]);
+        assert_eq!(sorted(grid.query(50.0, 50.0, 10.0)), vec![1]);
+        grid.remove(&2);
+        assert!(grid.query(0.0, 0.0, 5.0).is_empty());
+    }
+
+    #[test]
+    fn query_with_huge_radius_scans_occupied_cells() {
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert(1, 0.0, 0.0);
+        grid.insert(2, 1e6, -1e6);
+        assert_eq!(sorted(grid.query(0.0, 0.0, 1e7)), vec![1, 2]);
+        assert_eq!(sorted(grid.query(0.0, 0.0, ::std::f64::MAX)), vec![1, 2]);
+    }
+}
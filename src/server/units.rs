@@ -0,0 +1,397 @@
+use rustc_serialize::{json, Decodable};
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+use std::fs::{read_dir, File};
+use std::io::Read;
+use std::path::Path;
+
+use data_types::*;
+use server::damage::DamageTable;
+
+// Значения поля type у компонентов в описании типа
+const DRIVE_COMPONENT: &'static str = "Drive";
+const RADAR_COMPONENT: &'static str = "Radar";
+const WEAPON_COMPONENT: &'static str = "Weapon";
+const CARGO_COMPONENT: &'static str = "Cargo";
+const SHELL_COMPONENT: &'static str = "Shell";
+const BATTERY_COMPONENT: &'static str = "Battery";
+
+// Описание типа объекта: цена и время постройки, что он строит сам
+// и из каких компонентов состоит
+pub struct UnitDefinition {
+    pub otype: ObjectType,
+    pub name: String,
+    pub build_cost: f64,
+    pub build_time: f64,
+    pub build_types: Vec<ObjectType>,
+    pub components: Vec<Component>,
+}
+
+// Поля описания, кроме списка компонентов
+#[derive(RustcDecodable)]
+struct UnitHeader {
+    otype: ObjectType,
+    name: String,
+    build_cost: f64,
+    build_time: f64,
+    build_types: Vec<ObjectType>,
+}
+
+impl UnitDefinition {
+    pub fn can_build(&self, otype: &ObjectType) -> bool {
+        self.build_types.contains(otype)
+    }
+
+    // {"otype": ..., "components": [{"type": "Drive", ...}, ...]}
+    fn from_json(data: &Json) -> Result<Self, String> {
+        let header: UnitHeader = decode_json(data)?;
+        let items = match data.find("components").and_then(|items| items.as_array()) {
+            Some(items) => items,
+            None => return Err("components must be an array".to_owned()),
+        };
+        let mut components = vec![];
+        for (i, item) in items.iter().enumerate() {
+            let component = component_from_json(item)
+                .map_err(|e| format!("component #{}: {}", i, e))?;
+            components.push(component);
+        }
+        Ok(UnitDefinition {
+               otype: header.otype,
+               name: header.name,
+               build_cost: header.build_cost,
+               build_time: header.build_time,
+               build_types: header.build_types,
+               components: components,
+           })
+    }
+}
+
+fn component_from_json(item: &Json) -> Result<Component, String> {
+    let kind = match item.find("type").and_then(|kind| kind.as_string()) {
+        Some(kind) => kind.to_owned(),
+        None => return Err("missing component type".to_owned()),
+    };
+    match kind.as_str() {
+        DRIVE_COMPONENT => {
+            let item = with_state(item,
+                                  &[("speed", Json::F64(0.0)),
+                                    ("dest_x", Json::F64(0.0)),
+                                    ("dest_y", Json::F64(0.0))]);
+            Ok(Component::Drive(decode_json(&item)?))
+        }
+        RADAR_COMPONENT => Ok(Component::Radar(decode_json(item)?)),
+        WEAPON_COMPONENT => {
+            let item = with_state(item,
+                                  &[("cooldown", Json::F64(0.0)),
+                                    ("active", Json::Boolean(false)),
+                                    ("target_x", Json::F64(0.0)),
+                                    ("target_y", Json::F64(0.0))]);
+            Ok(Component::Weapon(decode_json(&item)?))
+        }
+        CARGO_COMPONENT => Ok(Component::Cargo(decode_json(item)?)),
+        SHELL_COMPONENT => Ok(Component::Shell(decode_json(item)?)),
+        BATTERY_COMPONENT => Ok(Component::Battery(decode_json(item)?)),
+        _ => Err(format!("unknown component type {}", kind)),
+    }
+}
+
+// Состояние нового объекта: скорость, цель движения, перезарядка и цель оружия.
+// В описании типа эти поля не пишутся, но и не запрещены
+fn with_state(item: &Json, state: &[(&str, Json)]) -> Json {
+    let mut item = item.clone();
+    if let Json::Object(ref mut fields) = item {
+        for &(field, ref value) in state.iter() {
+            fields.entry(field.to_owned()).or_insert_with(|| value.clone());
+        }
+    }
+    item
+}
+
+fn decode_json<T: Decodable>(item: &Json) -> Result<T, String> {
+    let mut decoder = json::Decoder::new(item.clone());
+    Decodable::decode(&mut decoder).map_err(|e| e.to_string())
+}
+
+// Описания типов объектов из каталога objects/, по файлу на тип.
+// Загружаются один раз при старте, новый тип - это новый файл
+pub struct UnitRegistry {
+    units: BTreeMap<ObjectType, UnitDefinition>,
+}
+
+impl UnitRegistry {
+    pub fn new(dir: &str, damage: &DamageTable) -> Result<Self, String> {
+        let entries = match read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => return Err(format!("Unit directory {} read error: {}", dir, e)),
+        };
+        let mut paths = vec![];
+        for entry in entries {
+            match entry {
+                Ok(entry) => paths.push(entry.path()),
+                Err(e) => return Err(format!("Unit directory {} read error: {}", dir, e)),
+            }
+        }
+        // Порядок файлов в каталоге не определён, а ошибки должны быть одинаковыми
+        paths.sort();
+
+        let mut units = BTreeMap::new();
+        let mut files: BTreeMap<ObjectType, String> = BTreeMap::new();
+        for path in paths {
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+            let name = path.display().to_string();
+            let unit = load_unit(&path)?;
+            if let Some(other) = files.get(&unit.otype) {
+                return Err(format!("Unit definitions {} and {} both describe type {}",
+                                   other,
+                                   name,
+                                   unit.otype.to_string()));
+            }
+            files.insert(unit.otype.clone(), name);
+            units.insert(unit.otype.clone(), unit);
+        }
+
+        let registry = UnitRegistry { units: units };
+        for (otype, unit) in registry.units.iter() {
+            if let Err(e) = registry.validate(unit, damage) {
+                return Err(format!("Unit definition {}: {}", files[otype], e));
+            }
+        }
+        for otype in [ObjectType::asteroid(), ObjectType::builder()].iter() {
+            if registry.get(otype).is_none() {
+                return Err(format!("Unit directory {} has no definition of {}",
+                                   dir,
+                                   otype.to_string()));
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn get(&self, otype: &ObjectType) -> Option<&UnitDefinition> {
+        self.units.get(otype)
+    }
+
+    fn validate(&self, unit: &UnitDefinition, damage: &DamageTable) -> Result<(), String> {
+        check_values(&[("build_cost", unit.build_cost), ("build_time", unit.build_time)])?;
+        for otype in unit.build_types.iter() {
+            if self.get(otype).is_none() {
+                return Err(format!("build_types has unknown type {}", otype.to_string()));
+            }
+        }
+
+        let mut single = BTreeMap::new();
+        for component in unit.components.iter() {
+            match *component {
+                Component::Drive(ref drive) => {
+                    *single.entry(DRIVE_COMPONENT).or_insert(0) += 1;
+                    check_values(&[("max_speed", drive.max_speed),
+                                   ("acceleration", drive.acceleration),
+                                   ("turn_rate", drive.turn_rate),
+                                   ("energy", drive.energy)])?;
+                }
+                Component::Radar(ref radar) => {
+                    check_values(&[("radius", radar.radius), ("energy", radar.energy)])?;
+                }
+                Component::Weapon(ref weapon) => {
+                    check_values(&[("radius", weapon.radius),
+                                   ("range", weapon.range),
+                                   ("damage", weapon.damage),
+                                   ("energy", weapon.energy)])?;
+                    if !damage.has_weapon(&weapon.wtype) {
+                        return Err(format!("weapon {} is missing from the damage table",
+                                           weapon.wtype.to_string()));
+                    }
+                    if !(weapon.rate > 0.0) || weapon.rate.is_infinite() {
+                        return Err("weapon rate must be greater than 0".to_owned());
+                    }
+                }
+                Component::Cargo(ref cargo) => {
+                    check_values(&[("max", cargo.max), ("current", cargo.current)])?;
+                    if cargo.current > cargo.max {
+                        return Err("cargo current must not exceed max".to_owned());
+                    }
+                }
+                Component::Shell(ref shell) => {
+                    *single.entry(SHELL_COMPONENT).or_insert(0) += 1;
+                    if !(shell.health > 0.0) || shell.health.is_infinite() {
+                        return Err("shell health must be greater than 0".to_owned());
+                    }
+                    if !damage.has_armor(&shell.armor) {
+                        return Err(format!("armor {} is missing from the damage table",
+                                           shell.armor.to_string()));
+                    }
+                }
+                Component::Battery(ref battery) => {
+                    check_values(&[("max", battery.max),
+                                   ("current", battery.current),
+                                   ("generation", battery.generation),
+                                   ("charge_radius", battery.charge_radius),
+                                   ("charge_rate", battery.charge_rate)])?;
+                    if battery.current > battery.max {
+                        return Err("battery current must not exceed max".to_owned());
+                    }
+                }
+            }
+        }
+        // Объект не может ехать двумя двигателями и получать урон двумя корпусами
+        for (kind, count) in single {
+            if count > 1 {
+                return Err(format!("more than one {} component", kind));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_values(values: &[(&str, f64)]) -> Result<(), String> {
+    for &(field, value) in values.iter() {
+        if !(value >= 0.0) || value.is_infinite() {
+            return Err(format!("{} must be a non-negative number, got {}", field, value));
+        }
+    }
+    Ok(())
+}
+
+fn load_unit(path: &Path) -> Result<UnitDefinition, String> {
+    let mut file = match File::open(path) {
+        Ok(data) => data,
+        Err(e) => return Err(format!("Unit definition {} open error: {}", path.display(), e)),
+    };
+    let mut string = String::new();
+    if let Err(e) = file.read_to_string(&mut string) {
+        return Err(format!("Unit definition {} read error: {}", path.display(), e));
+    }
+    let data = match Json::from_str(&string) {
+        Err(e) => return Err(format!("Unit definition {} parsing error: {}", path.display(), e)),
+        Ok(data) => data,
+    };
+    UnitDefinition::from_json(&data)
+        .map_err(|e| format!("Unit definition {} parsing error: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_serialize::json::Json;
+    use std::collections::BTreeMap;
+    use data_types::{Component, ObjectType};
+    use server::damage::DamageTable;
+    use super::{UnitDefinition, UnitRegistry};
+
+    const SHELL: &'static str = r#"{ "type": "Shell", "armor": "Light", "health": 100.0 }"#;
+    const DRIVE: &'static str = r#"{ "type": "Drive", "max_speed": 5.0, "acceleration": 1.0,
+                                     "turn_rate": 0.5, "energy": 0.5 }"#;
+
+    fn unit(otype: &str, build_types: &str, components: &[&str]) -> UnitDefinition {
+        let source = format!(r#"{{ "otype": "{}", "name": "{}", "build_cost": 10.0,
+                                   "build_time": 1.0, "build_types": {}, "components": [{}] }}"#,
+                             otype,
+                             otype,
+                             build_types,
+                             components.join(","));
+        UnitDefinition::from_json(&Json::from_str(&source).unwrap()).unwrap()
+    }
+
+    // Проверяет последний тип на фоне остальных
+    fn validate(units: Vec<UnitDefinition>) -> Result<(), String> {
+        let damage = DamageTable::new("config/damage.json").unwrap();
+        let otype = units.last().unwrap().otype.clone();
+        let registry = UnitRegistry {
+            units: units
+                .into_iter()
+                .map(|unit| (unit.otype.clone(), unit))
+                .collect::<BTreeMap<_, _>>(),
+        };
+        registry.validate(registry.get(&otype).unwrap(), &damage)
+    }
+
+    #[test]
+    fn registry_loads_objects_dir() {
+        let damage = DamageTable::new("config/damage.json").unwrap();
+        let registry = UnitRegistry::new("objects", &damage).unwrap();
+        assert!(registry.get(&ObjectType::builder()).is_some());
+        assert!(registry.get(&ObjectType::asteroid()).is_some());
+    }
+
+    #[test]
+    fn validate_accepts_correct_unit() {
+        assert!(validate(vec![unit("Scout", r#"["Scout"]"#, &[DRIVE, SHELL])]).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_build_type() {
+        let error = validate(vec![unit("Scout", r#"["Titan"]"#, &[SHELL])]).unwrap_err();
+        assert!(error.contains("Titan"));
+    }
+
+    #[test]
+    fn validate_rejects_duplicate_components() {
+        let error = validate(vec![unit("Scout", "[]", &[DRIVE, DRIVE])]).unwrap_err();
+        assert!(error.contains("Drive"));
+        assert!(validate(vec![unit("Scout", "[]", &[SHELL, SHELL])]).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_damage_classes() {
+        let shell = r#"{ "type": "Shell", "armor": "Paper", "health": 100.0 }"#;
+        let error = validate(vec![unit("Scout", "[]", &[shell])]).unwrap_err();
+        assert!(error.contains("Paper"));
+
+        let weapon = r#"{ "type": "Weapon", "wtype": "Plasma", "radius": 1.0,
+                          "range": 100.0, "damage": 10.0, "rate": 1.0, "energy": 1.0 }"#;
+        let error = validate(vec![unit("Scout", "[]", &[weapon])]).unwrap_err();
+        assert!(error.contains("Plasma"));
+    }
+
+    #[test]
+    fn validate_rejects_wrong_values() {
+        let drive = r#"{ "type": "Drive", "max_speed": -5.0, "acceleration": 1.0,
+                         "turn_rate": 0.5, "energy": 0.5 }"#;
+        let error = validate(vec![unit("Scout", "[]", &[drive])]).unwrap_err();
+        assert!(error.contains("max_speed"));
+
+        let cargo = r#"{ "type": "Cargo", "ctype": "Mining", "max": 10.0, "current": 20.0 }"#;
+        assert!(validate(vec![unit("Scout", "[]", &[cargo])]).is_err());
+    }
+
+    #[test]
+    fn from_json_rejects_unknown_component() {
+        let source = r#"{ "otype": "Scout", "name": "Scout", "build_cost": 10.0,
+                          "build_time": 1.0, "build_types": [],
+                          "components": [{ "type": "Teleport" }] }"#;
+        let error = UnitDefinition::from_json(&Json::from_str(source).unwrap())
+            .err()
+            .unwrap();
+        assert!(error.contains("Teleport"));
+    }
+
+    #[test]
+    fn from_json_fills_runtime_state() {
+        let weapon = r#"{ "type": "Weapon", "wtype": "Laser", "radius": 1.0,
+                          "range": 100.0, "damage": 10.0, "rate": 1.0, "energy": 1.0 }"#;
+        let unit = unit("Scout", "[]", &[DRIVE, weapon]);
+        match unit.components[0] {
+            Component::Drive(ref drive) => {
+                assert_eq!((drive.speed, drive.dest_x, drive.dest_y), (0.0, 0.0, 0.0));
+            }
+            _ => panic!("expected drive"),
+        }
+        match unit.components[1] {
+            Component::Weapon(ref weapon) => {
+                assert!(!weapon.active);
+                assert_eq!(weapon.cooldown, 0.0);
+            }
+            _ => panic!("expected weapon"),
+        }
+    }
+
+    #[test]
+    fn from_json_keeps_explicit_state() {
+        let drive = r#"{ "type": "Drive", "speed": 2.0, "max_speed": 5.0, "acceleration": 1.0,
+                         "turn_rate": 0.5, "energy": 0.5 }"#;
+        match unit("Scout", "[]", &[drive]).components[0] {
+            Component::Drive(ref drive) => assert_eq!(drive.speed, 2.0),
+            _ => panic!("expected drive"),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar};
+use data_types::{ObjectId, ObjectResponse, WorldUpdate, GameEventResponse};
+
+// Сколько последних обновлений хранится для отстающих клиентов (10 секунд при 60 TPS)
+const HISTORY_SIZE: usize = 600;
+
+// Мир, каким его видит один подписчик, и история его изменений
+struct View {
+    revision: u64,
+    history: VecDeque<WorldUpdate>,
+    published: HashMap<ObjectId, ObjectResponse>,
+}
+
+// Обновления мира для /updates. У каждого подписчика своя история: игрок
+// получает только то, что ему показывает /objects, а объект, пропавший с его
+// радаров, приходит в removed. События достаются тем, кто видел их объект
+pub struct UpdateLog {
+    views: HashMap<String, View>,
+    events: Vec<GameEventResponse>,
+    // Будит long-poll запросы после публикации, ждать на нём можно только
+    // с мьютексом движка
+    published: Arc<Condvar>,
+    // Сколько long-poll запросов сейчас ждёт, каждый занимает поток сервера
+    pub waiting: usize,
+}
+
+impl View {
+    fn new() -> Self {
+        View {
+            revision: 0,
+            history: VecDeque::new(),
+            published: HashMap::new(),
+        }
+    }
+
+    // Сравнивает видимые объекты с последним опубликованным состоянием и записывает разницу
+    fn publish(&mut self, objects: Vec<ObjectResponse>, events: &[GameEventResponse]) {
+        let mut visible = HashMap::new();
+        for object in objects {
+            visible.insert(object.id, object);
+        }
+        let mut changed = vec![];
+        for (id, object) in visible.iter() {
+            if self.published.get(id) != Some(object) {
+                changed.push(object.clone());
+            }
+        }
+        let removed: Vec<ObjectId> = self.published
+            .keys()
+            .filter(|id| !visible.contains_key(*id))
+            .cloned()
+            .collect();
+        let events: Vec<GameEventResponse> = events
+            .iter()
+            .filter(|event| visible.contains_key(&event.id) || removed.contains(&event.id))
+            .cloned()
+            .collect();
+        self.published = visible;
+
+        if changed.is_empty() && removed.is_empty() && events.is_empty() {
+            return;
+        }
+        self.revision += 1;
+        self.history
+            .push_back(WorldUpdate {
+                           revision: self.revision,
+                           changed: changed,
+                           removed: removed,
+                           events: events,
+                       });
+        if self.history.len() > HISTORY_SIZE {
+            self.history.pop_front();
+        }
+    }
+}
+
+impl UpdateLog {
+    pub fn new() -> Self {
+        UpdateLog {
+            views: HashMap::new(),
+            events: vec![],
+            published: Arc::new(Condvar::new()),
+            waiting: 0,
+        }
+    }
+
+    pub fn push_event(&mut self, kind: &str, id: ObjectId) {
+        self.events
+            .push(GameEventResponse {
+                      kind: kind.to_owned(),
+                      id: id,
+                  });
+    }
+
+    pub fn subscribed(&self, viewer: &String) -> bool {
+        self.views.contains_key(viewer)
+    }
+
+    // Заводит историю подписчика, objects - то, что он видит сейчас
+    pub fn subscribe(&mut self, viewer: &String, objects: Vec<ObjectResponse>) {
+        let mut view = View::new();
+        view.publish(objects, &[]);
+        self.views.insert(viewer.clone(), view);
+    }
+
+    pub fn viewers(&self) -> Vec<String> {
+        self.views.keys().cloned().collect()
+    }
+
+    // Публикует то, что каждый подписчик видит в конце тика, вместе с событиями тика
+    pub fn publish(&mut self, visible: Vec<(String, Vec<ObjectResponse>)>) {
+        for (viewer, objects) in visible {
+            if let Some(view) = self.views.get_mut(&viewer) {
+                view.publish(objects, &self.events);
+            }
+        }
+        self.events.clear();
+        self.published.notify_all();
+    }
+
+    pub fn notifier(&self) -> Arc<Condvar> {
+        self.published.clone()
+    }
+
+    pub fn revision(&self, viewer: &String) -> u64 {
+        self.views.get(viewer).map_or(0, |view| view.revision)
+    }
+
+    // Обновления после revision или None, если они уже вытеснены из истории
+    // или revision из будущего (клиент пережил перезапуск сервера)
+    pub fn since(&self, viewer: &String, revision: u64) -> Option<Vec<WorldUpdate>> {
+        let view = match self.views.get(viewer) {
+            Some(view) => view,
+            None => return None,
+        };
+        if revision > view.revision {
+            return None;
+        }
+        if revision == view.revision {
+            return Some(vec![]);
+        }
+        match view.history.front() {
+            Some(first) if first.revision <= revision + 1 => {
+                Some(view.history
+                         .iter()
+                         .filter(|update| update.revision > revision)
+                         .cloned()
+                         .collect())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn snapshot(&self, viewer: &String) -> WorldUpdate {
+        WorldUpdate {
+            revision: self.revision(viewer),
+            changed: self.views
+                .get(viewer)
+                .map_or(vec![], |view| view.published.values().cloned().collect()),
+            removed: vec![],
+            events: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_types::{SampleObject, ObjectId, ObjectResponse, ObjectType};
+    use super::{UpdateLog, HISTORY_SIZE};
+
+    fn world(objects: &[(ObjectId, f64)]) -> Vec<ObjectResponse> {
+        objects
+            .iter()
+            .map(|&(id, x)| {
+                     SampleObject::new(id,
+                                       "Player0".to_owned(),
+                                       "Builder".to_owned(),
+                                       ObjectType::builder(),
+                                       x,
+                                       0.0)
+                             .to_response()
+                 })
+            .collect()
+    }
+
+    fn publish(log: &mut UpdateLog, viewer: &String, objects: &[(ObjectId, f64)]) {
+        log.publish(vec![(viewer.clone(), world(objects))]);
+    }
+
+    #[test]
+    fn subscribe_publishes_current_world() {
+        let player = "Player0".to_owned();
+        let mut log = UpdateLog::new();
+        assert!(log.since(&player, 0).is_none());
+        log.subscribe(&player, world(&[(1, 0.0)]));
+        assert_eq!(log.revision(&player), 1);
+        assert_eq!(log.snapshot(&player).changed.len(), 1);
+    }
+
+    #[test]
+    fn publish_records_only_changes() {
+        let player = "Player0".to_owned();
+        let mut log = UpdateLog::new();
+        log.subscribe(&player, vec![]);
+        publish(&mut log, &player, &[(1, 0.0), (2, 0.0)]);
+        assert_eq!(log.revision(&player), 1);
+
+        // Без изменений ревизия не растёт
+        publish(&mut log, &player, &[(1, 0.0), (2, 0.0)]);
+        assert_eq!(log.revision(&player), 1);
+
+        publish(&mut log, &player, &[(1, 5.0)]);
+        assert_eq!(log.revision(&player), 2);
+        let updates = log.since(&player, 1).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].changed.len(), 1);
+        assert_eq!(updates[0].changed[0].id, 1);
+        assert_eq!(updates[0].removed, vec![2]);
+    }
+
+    #[test]
+    fn viewers_get_separate_histories() {
+        let first = "Player0".to_owned();
+        let second = "Player1".to_owned();
+        let mut log = UpdateLog::new();
+        log.subscribe(&first, vec![]);
+        log.subscribe(&second, vec![]);
+        log.publish(vec![(first.clone(), world(&[(1, 0.0)])),
+                         (second.clone(), world(&[(2, 0.0)]))]);
+        assert_eq!(log.since(&first, 0).unwrap()[0].changed[0].id, 1);
+        assert_eq!(log.since(&second, 0).unwrap()[0].changed[0].id, 2);
+
+        // Объект пропал с радаров второго игрока, но не из мира
+        log.publish(vec![(first.clone(), world(&[(1, 0.0), (2, 0.0)])),
+                         (second.clone(), vec![])]);
+        assert_eq!(log.since(&second, 1).unwrap()[0].removed, vec![2]);
+        assert_eq!(log.since(&first, 1).unwrap()[0].changed[0].id, 2);
+    }
+
+    #[test]
+    fn events_reach_only_viewers_of_their_object() {
+        let first = "Player0".to_owned();
+        let second = "Player1".to_owned();
+        let mut log = UpdateLog::new();
+        log.subscribe(&first, world(&[(1, 0.0)]));
+        log.subscribe(&second, world(&[(2, 0.0)]));
+        log.push_event("Destroyed", 1);
+        log.publish(vec![(first.clone(), vec![]), (second.clone(), world(&[(2, 0.0)]))]);
+        assert_eq!(log.since(&first, 1).unwrap()[0].events.len(), 1);
+        assert_eq!(log.revision(&second), 1);
+
+        // События без изменений тоже дают новую ревизию
+        log.push_event("Built", 2);
+        log.publish(vec![(second.clone(), world(&[(2, 0.0)]))]);
+        assert_eq!(log.since(&second, 1).unwrap()[0].events.len(), 1);
+    }
+
+    #[test]
+    fn since_returns_missed_updates() {
+        let player = "Player0".to_owned();
+        let mut log = UpdateLog::new();
+        log.subscribe(&player, vec![]);
+        for x in 1..4 {
+            publish(&mut log, &player, &[(1, x as f64)]);
+        }
+        assert_eq!(log.since(&player, 0).unwrap().len(), 3);
+        let updates = log.since(&player, 1).unwrap();
+        assert_eq!(updates.iter().map(|u| u.revision).collect::<Vec<_>>(),
+                   vec![2, 3]);
+        assert!(log.since(&player, 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn since_rejects_unknown_revisions() {
+        let player = "Player0".to_owned();
+        let mut log = UpdateLog::new();
+        log.subscribe(&player, vec![]);
+        for x in 0..HISTORY_SIZE + 2 {
+            publish(&mut log, &player, &[(1, x as f64)]);
+        }
+        // Начало истории уже вытеснено
+        assert!(log.since(&player, 0).is_none());
+        assert!(log.since(&player, 2).is_some());
+        // Клиент впереди сервера
+        assert!(log.since(&player, log.revision(&player) + 1).is_none());
+    }
+}
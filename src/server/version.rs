@@ -0,0 +1,6 @@
+// Поддерживаемые версии API, последняя - текущая. Маршрут, формат которого
+// меняется в новой версии, регистрируется отдельно для старых и новых версий
+// (RouteSpec::versions), остальные обслуживаются во всех версиях из списка
+pub const API_VERSIONS: &'static [u32] = &[1];
+// Версия, которую обслуживают пути без префикса /vN
+pub const LEGACY_API_VERSION: u32 = 1;